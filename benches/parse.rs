@@ -0,0 +1,80 @@
+//! Parsing speed on "The demo", with the project files held in memory so that only parsing
+//! is measured. Run with `cargo bench`.
+//!
+//! To compare with an earlier revision, check it out with `git worktree add`, copy this file
+//! into its `benches` directory with a `[[bench]]` entry (dropping the benches of APIs it
+//! doesn't have yet), and run both on the same machine. The Reader rewrite that came with this
+//! benchmark was measured that way, against the revision before it: "project" went from
+//! 2.1-2.4ms to 1.8-1.9ms, and "pattern files" from 1.7-1.8ms to 1.5-1.8ms.
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use play_files::*;
+
+const ITERATIONS: u32 = 200;
+
+fn project_files(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+    let mut files = BTreeMap::new();
+    for sub_dir in ["", "samples", "patterns"] {
+        for entry in std::fs::read_dir(dir.join(sub_dir)).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_file() {
+                let name = entry.file_name().into_string().unwrap();
+                let path = if sub_dir.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", sub_dir, name)
+                };
+                files.insert(path, std::fs::read(entry.path()).unwrap());
+            }
+        }
+    }
+    files
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // Warm up
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let each: Duration = start.elapsed() / ITERATIONS;
+    println!("{:<24} {:>10.1?} per iteration", name, each);
+}
+
+fn main() {
+    let files = project_files(Path::new("./examples/projects/The demo"));
+
+    bench("project", || {
+        black_box(Project::from_files(black_box(&files)).unwrap());
+    });
+    #[cfg(feature = "rayon")]
+    bench("project (parallel)", || {
+        black_box(Project::from_files_par(black_box(&files)).unwrap());
+    });
+    bench("pattern files", || {
+        for (path, bytes) in &files {
+            if let Some(name) = path.strip_prefix("patterns/") {
+                if name.ends_with(".pattern") {
+                    black_box(Pattern::from_bytes(black_box(bytes), 0).unwrap());
+                }
+            }
+        }
+    });
+    bench("step refs", || {
+        for (path, bytes) in &files {
+            if let Some(name) = path.strip_prefix("patterns/") {
+                if name.ends_with(".pattern") {
+                    for track in 0..8 {
+                        let steps = StepRef::pattern_file(black_box(bytes), track).unwrap();
+                        black_box(steps.iter().filter(|s| !s.is_empty()).count());
+                    }
+                }
+            }
+        }
+    });
+}
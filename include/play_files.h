@@ -0,0 +1,191 @@
+#ifndef PLAY_FILES_H
+#define PLAY_FILES_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct PfMidiStep PfMidiStep;
+
+typedef struct PfPattern PfPattern;
+
+// A project opened with `pf_project_open`
+typedef struct PfProject PfProject;
+
+typedef struct PfStep PfStep;
+
+typedef struct PfMidiTrack PfMidiTrack;
+
+typedef struct PfTrack PfTrack;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last error on this thread, or NULL if there hasn't been one. Valid until
+// the next call that fails.
+const char *pf_last_error(void);
+
+// Read the project in the directory `path`. Returns NULL if it can't be read.
+//
+// # Safety
+//
+// `path` must be NULL or a NUL-terminated string.
+struct PfProject *pf_project_open(const char *path);
+
+// Free a project, and everything borrowed from it
+void pf_project_free(struct PfProject *project);
+
+// The name of the project. Valid until the project is freed.
+const char *pf_project_name(const struct PfProject *project);
+
+// Beats per minute
+float pf_project_bpm(const struct PfProject *project);
+
+size_t pf_project_pattern_count(const struct PfProject *project);
+
+// The pattern at `index`, from 0 to `pf_project_pattern_count` - 1. Patterns are in the order
+// of their files, which isn't always the order of their numbers.
+const struct PfPattern *pf_project_pattern(const struct PfProject *project, size_t index);
+
+// The number of the pattern, from 0
+uint8_t pf_pattern_number(const struct PfPattern *pattern);
+
+// The variation of audio track `track` (0-7) that was active when the pattern was saved
+const struct PfTrack *pf_pattern_audio_track(const struct PfPattern *pattern, size_t track);
+
+// Variation `variation` (0-15) of audio track `track` (0-7), or NULL if the pattern doesn't
+// have it
+const struct PfTrack *pf_pattern_audio_variation(const struct PfPattern *pattern,
+                                                 size_t track,
+                                                 size_t variation);
+
+// The variation of MIDI track `track` (0-7) that was active when the pattern was saved
+const struct PfMidiTrack *pf_pattern_midi_track(const struct PfPattern *pattern, size_t track);
+
+// Variation `variation` (0-15) of MIDI track `track` (0-7), or NULL if the pattern doesn't
+// have it
+const struct PfMidiTrack *pf_pattern_midi_variation(const struct PfPattern *pattern,
+                                                    size_t track,
+                                                    size_t variation);
+
+// The number of steps of the track, from 1 to 64
+size_t pf_track_step_count(const struct PfTrack *track);
+
+// The step at `index`, from 0 to `pf_track_step_count` - 1
+const struct PfStep *pf_track_step(const struct PfTrack *track, size_t index);
+
+size_t pf_track_variation(const struct PfTrack *track);
+
+// Swing in percent, 25-75
+uint8_t pf_track_swing(const struct PfTrack *track);
+
+// Steps per beat, relative to the project's: e.g. 2.0 for 2/1. 0 when paused.
+float pf_track_speed(const struct PfTrack *track);
+
+// The number of steps of the track, from 1 to 64
+size_t pf_midi_track_step_count(const struct PfMidiTrack *track);
+
+// The step at `index`, from 0 to `pf_midi_track_step_count` - 1
+const struct PfMidiStep *pf_midi_track_step(const struct PfMidiTrack *track, size_t index);
+
+size_t pf_midi_track_variation(const struct PfMidiTrack *track);
+
+// Swing in percent, 25-75
+uint8_t pf_midi_track_swing(const struct PfMidiTrack *track);
+
+// Steps per beat, relative to the project's: e.g. 2.0 for 2/1. 0 when paused.
+float pf_midi_track_speed(const struct PfMidiTrack *track);
+
+// Whether there is no note on the step
+bool pf_step_is_empty(const struct PfStep *step);
+
+// The sample slot, from 0
+uint16_t pf_step_sample(const struct PfStep *step);
+
+// MIDI note number, where 60 is C4
+uint8_t pf_step_note(const struct PfStep *step);
+
+// Volume in dB: -INFINITY when silent
+float pf_step_volume_db(const struct PfStep *step);
+
+// Pan in percent, from -100 (hard left) to 100 (hard right)
+float pf_step_pan(const struct PfStep *step);
+
+// Filter cutoff in percent, from -100 (LP100) to 100 (HP100). 0 is off.
+float pf_step_filter_cutoff(const struct PfStep *step);
+
+// Filter resonance in percent
+float pf_step_filter_resonance(const struct PfStep *step);
+
+// Overdrive in percent
+float pf_step_overdrive(const struct PfStep *step);
+
+// Bit depth, 4-16
+uint8_t pf_step_bit_depth(const struct PfStep *step);
+
+// Micro move in 24ths of a step, from -11 to 11
+int16_t pf_step_micro_move(const struct PfStep *step);
+
+// Micro tune in cents, from -100 to 100
+float pf_step_micro_tune(const struct PfStep *step);
+
+// Reverb send in percent
+float pf_step_reverb(const struct PfStep *step);
+
+// Delay send in percent
+float pf_step_delay(const struct PfStep *step);
+
+// Where the sample starts playing, from 0.0 (its start) to 1.0 (its end)
+float pf_step_sample_start(const struct PfStep *step);
+
+// Where the sample stops playing, from 0.0 (its start) to 1.0 (its end)
+float pf_step_sample_end(const struct PfStep *step);
+
+// Sample attack in percent
+float pf_step_sample_attack(const struct PfStep *step);
+
+// Sample decay in percent
+float pf_step_sample_decay(const struct PfStep *step);
+
+// The raw chance type: 0 is always
+uint16_t pf_step_chance_type(const struct PfStep *step);
+
+// The raw repeat type: 0 is off
+uint16_t pf_step_repeat_type(const struct PfStep *step);
+
+// Whether there is no note on the step
+bool pf_midi_step_is_empty(const struct PfMidiStep *step);
+
+// MIDI note number, where 60 is C4
+uint8_t pf_midi_step_note(const struct PfMidiStep *step);
+
+uint8_t pf_midi_step_velocity(const struct PfMidiStep *step);
+
+// MIDI channel, from 1 to 16, on the jack or USB as given by `pf_midi_step_is_usb`
+uint8_t pf_midi_step_channel(const struct PfMidiStep *step);
+
+// Whether the step is sent over USB rather than the MIDI jack
+bool pf_midi_step_is_usb(const struct PfMidiStep *step);
+
+// Note length as shown on the device
+float pf_midi_step_note_length(const struct PfMidiStep *step);
+
+// Micro move in 24ths of a step, from -11 to 11
+int16_t pf_midi_step_micro_move(const struct PfMidiStep *step);
+
+// Program change, or -1 if the step doesn't send one
+int16_t pf_midi_step_program(const struct PfMidiStep *step);
+
+// Pitch bend in cents, or 0 if the step doesn't send one
+float pf_midi_step_pitch_bend(const struct PfMidiStep *step);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PLAY_FILES_H */
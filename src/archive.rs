@@ -0,0 +1,150 @@
+//! Reading and writing projects in zip archives, as they are shared.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::vfs::{self, names_in, project_files};
+use crate::{ParseError, Project, Result, SampleFile, Vfs};
+
+impl SampleFile {
+    /// Read the sample files of the project in the zip archive at `path`
+    #[cfg(feature = "fs")]
+    pub fn read_zip(path: &Path) -> Result<Vec<Self>> {
+        Self::from_zip(open(path)?)
+    }
+
+    /// Read the sample files of the project in a zip archive
+    pub fn from_zip(source: impl Read + Seek) -> Result<Vec<Self>> {
+        Self::from_files(&ZipFiles::from_zip(source)?)
+            .map_err(|e| ParseError(format!("Cannot read samples from zip: {}", e)))
+    }
+}
+
+#[cfg(feature = "fs")]
+fn open(path: &Path) -> Result<File> {
+    File::open(path).map_err(|e| ParseError(format!("Cannot open {:?}: {}", path, e)))
+}
+
+/// The files of a project in a zip archive.
+///
+/// The files are held in memory, by their path in the project directory: they are read from
+/// an archive with [`ZipFiles::from_zip`], and written to one with [`ZipFiles::to_zip`].
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct ZipFiles {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl ZipFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the project files of a zip archive.
+    ///
+    /// The project may be at the top of the archive, or in a folder, as when a project
+    /// directory is zipped. Directories, and the hidden files that macOS adds to archives, are
+    /// left out.
+    pub fn from_zip(source: impl Read + Seek) -> Result<Self> {
+        let mut archive =
+            ZipArchive::new(source).map_err(|e| ParseError(format!("Cannot read zip: {}", e)))?;
+        let mut files = BTreeMap::new();
+        for i in 0..archive.len() {
+            let mut file = archive
+                .by_index(i)
+                .map_err(|e| ParseError(format!("Cannot read zip: {}", e)))?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name().to_string();
+            let mut bytes = vec![];
+            file.read_to_end(&mut bytes)
+                .map_err(|e| ParseError(format!("Cannot read {} from zip: {}", name, e)))?;
+            files.insert(name, bytes);
+        }
+        Ok(Self {
+            files: project_files(files, "zip")?,
+        })
+    }
+
+    /// Write the files to a zip archive, in the folder `folder`
+    pub fn to_zip<W: Write + Seek>(&self, writer: W, folder: &str) -> io::Result<W> {
+        let mut zip = ZipWriter::new(writer);
+        let options = SimpleFileOptions::default();
+        for (name, bytes) in &self.files {
+            zip.start_file(format!("{}/{}", folder, name), options)?;
+            zip.write_all(bytes)?;
+        }
+        Ok(zip.finish()?)
+    }
+}
+
+impl Vfs for ZipFiles {
+    fn read_file(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.files.get(path).cloned())
+    }
+
+    fn list_dir(&self, dir: &str) -> io::Result<Vec<String>> {
+        Ok(names_in(self.files.keys(), dir))
+    }
+
+    fn write_file(&mut self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        self.files.insert(path.to_string(), bytes.to_vec());
+        Ok(())
+    }
+}
+
+impl Project {
+    /// Read a project from the zip archive at `path`, without extracting it
+    #[cfg(feature = "fs")]
+    pub fn read_zip(path: &Path) -> Result<Self> {
+        Self::from_zip(open(path)?)
+    }
+
+    /// Read a project from a zip archive. The project files may be at the top of the archive
+    /// or in a single folder.
+    pub fn from_zip(source: impl Read + Seek) -> Result<Self> {
+        Self::from_files(&ZipFiles::from_zip(source)?)
+    }
+
+    /// Write the project, and the given sample files, to a zip archive at `path`
+    #[cfg(feature = "fs")]
+    pub fn write_zip(&self, path: &Path, samples: &[SampleFile]) -> Result<()> {
+        let file = File::create(path)
+            .map_err(|e| ParseError(format!("Cannot create {:?}: {}", path, e)))?;
+        self.to_zip(file, samples)?;
+        Ok(())
+    }
+
+    /// Write the project, and the given sample files, as a zip archive.
+    ///
+    /// The files are in a folder named after the project, as when a project directory is
+    /// zipped. Separators and leading dots are left out of the name, so that the folder is
+    /// neither hidden, which [from_zip](Self::from_zip) would skip, nor outside of where the
+    /// archive is extracted.
+    pub fn to_zip<W: Write + Seek>(&self, writer: W, samples: &[SampleFile]) -> Result<W> {
+        let name = self.settings.name.replace(['/', '\\'], "_");
+        let folder = match name.trim_start_matches('.') {
+            "" => "project",
+            name => name,
+        };
+        let mut files = ZipFiles::new();
+        self.write_files(&mut files)?;
+        for sample in samples {
+            vfs::write_file(
+                &mut files,
+                &format!("samples/{}", sample.file_name),
+                &sample.bytes,
+            )?;
+        }
+        files
+            .to_zip(writer, folder)
+            .map_err(|e| ParseError(format!("Cannot write zip archive: {}", e)))
+    }
+}
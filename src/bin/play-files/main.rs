@@ -0,0 +1,328 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use play_files::{Change, Conflict, Pattern, Project, SampleFile};
+
+mod print;
+
+/// Inspect and convert Polyend Play projects
+#[derive(Parser)]
+#[command(name = "play-files", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Summarize a project
+    Info { project: PathBuf },
+    /// Print a project's settings, including its MIDI CC mappings
+    DumpSettings { project: PathBuf },
+    /// Print the active variation of every track in a pattern
+    DumpPattern { project: PathBuf, pattern: u8 },
+    /// Print a single track variation (by default the active one).
+    ///
+    /// Tracks 0-7 are audio tracks and 8-15 are MIDI tracks, as in the names of track files.
+    DumpTrack {
+        project: PathBuf,
+        pattern: u8,
+        track: usize,
+        variation: Option<usize>,
+    },
+    /// Print a project as JSON or YAML
+    Export {
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        project: PathBuf,
+    },
+    /// Create a project directory from a JSON or YAML export
+    Import {
+        /// Defaults to the format matching the file's extension
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        file: PathBuf,
+        out_dir: PathBuf,
+    },
+    /// Check a project for problems before copying it to the device.
+    ///
+    /// Each problem is printed as a JSON object on its own line, with the fields `location`,
+    /// `field` and `message`. Exits with status 1 if there are any problems.
+    Lint { project: PathBuf },
+    /// List the changes between two projects, including replaced sample files
+    Diff { a: PathBuf, b: PathBuf },
+    /// Combine the changes made to a project in two copies of it.
+    ///
+    /// Where both copies changed a value differently, the value from OURS is kept and the
+    /// conflict is reported.
+    Merge {
+        base: PathBuf,
+        ours: PathBuf,
+        theirs: PathBuf,
+        out_dir: PathBuf,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Format {
+    Json,
+    Yaml,
+}
+
+fn read_project(path: &Path) -> Result<Project, String> {
+    Project::read(path).map_err(|e| e.to_string())
+}
+
+fn find_pattern(project: &Project, number: u8) -> Result<&Pattern, String> {
+    project
+        .pattern(number)
+        .ok_or_else(|| format!("No pattern number {}", number))
+}
+
+fn export(project: &Project, format: Format) -> Result<String, String> {
+    match format {
+        Format::Json => serde_json::to_string_pretty(project).map_err(|e| e.to_string()),
+        Format::Yaml => serde_yaml::to_string(project).map_err(|e| e.to_string()),
+    }
+}
+
+fn import(file: &Path, out_dir: &Path, format: Option<Format>) -> Result<(), String> {
+    let format = match format {
+        Some(f) => f,
+        None => match file.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            Some("yaml") | Some("yml") => Format::Yaml,
+            _ => return Err(format!("Unknown format of {:?}; use --format", file)),
+        },
+    };
+    let text = std::fs::read_to_string(file).map_err(|e| format!("{:?}: {}", file, e))?;
+    let project: Project = match format {
+        Format::Json => serde_json::from_str(&text).map_err(|e| e.to_string())?,
+        Format::Yaml => serde_yaml::from_str(&text).map_err(|e| e.to_string())?,
+    };
+
+    let violations = project.check_ranges();
+    if !violations.is_empty() {
+        let lines: Vec<String> = violations.iter().map(|v| format!("  {}", v)).collect();
+        return Err(format!(
+            "{} values are out of range:\n{}",
+            violations.len(),
+            lines.join("\n")
+        ));
+    }
+
+    check_empty(out_dir)?;
+    project
+        .write(out_dir)
+        .map_err(|e| format!("{:?}: {}", out_dir, e))
+}
+
+fn check_empty(dir: &Path) -> Result<(), String> {
+    if dir
+        .read_dir()
+        .map(|mut d| d.next().is_some())
+        .unwrap_or(false)
+    {
+        return Err(format!("{:?} is not empty", dir));
+    }
+    Ok(())
+}
+
+/// The name and contents of each sample file, by slot
+fn sample_files(project: &Path) -> Result<BTreeMap<u16, (String, Vec<u8>)>, String> {
+    let files = SampleFile::read_dir(project).map_err(|e| format!("{:?}: {}", project, e))?;
+    Ok(files
+        .into_iter()
+        .map(|f| (f.slot, (f.file_name, f.bytes)))
+        .collect())
+}
+
+fn diff_samples(a: &Path, b: &Path) -> Result<Vec<Change>, String> {
+    let (a, b) = (sample_files(a)?, sample_files(b)?);
+    let mut changes = vec![];
+    let slots: BTreeSet<&u16> = a.keys().chain(b.keys()).collect();
+    for slot in slots {
+        let location = format!("sample slot {}", slot);
+        match (a.get(slot), b.get(slot)) {
+            (Some(_), None) => changes.push(Change::Removed(location)),
+            (None, Some(_)) => changes.push(Change::Added(location)),
+            (Some((na, ba)), Some((nb, bb))) if na != nb || ba != bb => {
+                changes.push(Change::Changed {
+                    location,
+                    field: "file".to_string(),
+                    old: na.clone(),
+                    new: if ba == bb {
+                        nb.clone()
+                    } else {
+                        format!("{} (replaced)", nb)
+                    },
+                })
+            }
+            _ => (),
+        }
+    }
+    Ok(changes)
+}
+
+/// Merge the sample files of each slot, keeping those of `ours` when both sides changed a slot
+fn merge_samples(
+    base: &Path,
+    ours: &Path,
+    theirs: &Path,
+    out_dir: &Path,
+) -> Result<Vec<Conflict>, String> {
+    let (base, ours, theirs) = (
+        sample_files(base)?,
+        sample_files(ours)?,
+        sample_files(theirs)?,
+    );
+    let slots: BTreeSet<&u16> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+    let mut conflicts = vec![];
+    for slot in slots {
+        let (b, o, t) = (base.get(slot), ours.get(slot), theirs.get(slot));
+        let file = if o == t || b == t {
+            o
+        } else if b == o {
+            t
+        } else {
+            let name = |f: Option<&(String, Vec<u8>)>| match f {
+                Some((name, _)) => name.clone(),
+                None => "absent".to_string(),
+            };
+            conflicts.push(Conflict {
+                location: format!("sample slot {}", slot),
+                field: "file".to_string(),
+                base: name(b),
+                ours: name(o),
+                theirs: name(t),
+            });
+            o
+        };
+        if let Some((name, bytes)) = file {
+            let path = out_dir.join("samples").join(name);
+            fs::write(&path, bytes).map_err(|e| format!("{:?}: {}", path, e))?;
+        }
+    }
+    Ok(conflicts)
+}
+
+fn merge(base: &Path, ours: &Path, theirs: &Path, out_dir: &Path) -> Result<(), String> {
+    let (project, mut conflicts) = play_files::merge(
+        &read_project(base)?,
+        &read_project(ours)?,
+        &read_project(theirs)?,
+    );
+    check_empty(out_dir)?;
+    project
+        .write(out_dir)
+        .map_err(|e| format!("{:?}: {}", out_dir, e))?;
+    conflicts.extend(merge_samples(base, ours, theirs, out_dir)?);
+    if !conflicts.is_empty() {
+        let lines: Vec<String> = conflicts.iter().map(|c| format!("  {}", c)).collect();
+        return Err(format!(
+            "{} conflicts; kept the values from {:?}:\n{}",
+            conflicts.len(),
+            ours,
+            lines.join("\n")
+        ));
+    }
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), String> {
+    match cli.command {
+        Command::Info { project } => print::info(&read_project(&project)?),
+        Command::DumpSettings { project } => print::settings(&read_project(&project)?.settings),
+        Command::DumpPattern { project, pattern } => {
+            let project = read_project(&project)?;
+            print::pattern(find_pattern(&project, pattern)?)
+        }
+        Command::DumpTrack {
+            project,
+            pattern,
+            track,
+            variation,
+        } => {
+            let project = read_project(&project)?;
+            let pattern = find_pattern(&project, pattern)?;
+            if track >= 16 {
+                return Err(format!("Invalid track number {}; expected 0-15", track));
+            }
+            let found = match (track < 8, variation) {
+                (true, Some(v)) => pattern.audio_tracks[track]
+                    .get(v)
+                    .and_then(|t| t.as_ref())
+                    .map(print::audio_track),
+                (true, None) => pattern.active_audio_track(track).map(print::audio_track),
+                (false, Some(v)) => pattern.midi_tracks[track - 8]
+                    .get(v)
+                    .and_then(|t| t.as_ref())
+                    .map(print::midi_track),
+                (false, None) => pattern.active_midi_track(track - 8).map(print::midi_track),
+            };
+            if found.is_none() {
+                return Err(match variation {
+                    Some(v) => format!(
+                        "No variation {} of track {} in pattern {}",
+                        v, track, pattern.number
+                    ),
+                    None => format!(
+                        "Track {} has no active variation in pattern {}",
+                        track, pattern.number
+                    ),
+                });
+            }
+        }
+        Command::Export { format, project } => {
+            println!("{}", export(&read_project(&project)?, format)?)
+        }
+        Command::Import {
+            format,
+            file,
+            out_dir,
+        } => import(&file, &out_dir, format)?,
+        Command::Lint { project } => {
+            let violations = Project::validate_dir(&project).map_err(|e| e.to_string())?;
+            for violation in &violations {
+                println!(
+                    "{}",
+                    serde_json::to_string(violation).map_err(|e| e.to_string())?
+                );
+            }
+            if !violations.is_empty() {
+                return Err(format!("{} problems found", violations.len()));
+            }
+        }
+        Command::Diff { a, b } => {
+            let mut changes = play_files::diff(&read_project(&a)?, &read_project(&b)?);
+            changes.extend(diff_samples(&a, &b)?);
+            if changes.is_empty() {
+                println!("No changes");
+            }
+            for change in changes {
+                println!("{}", change);
+            }
+        }
+        Command::Merge {
+            base,
+            ours,
+            theirs,
+            out_dir,
+        } => merge(&base, &ours, &theirs, &out_dir)?,
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
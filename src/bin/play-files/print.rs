@@ -0,0 +1,251 @@
+//! Human readable output of project data: one compact table row per step.
+
+use play_files::units::*;
+use play_files::{CCMapping, MidiStep, Pattern, PlayMode, Project, Settings, Step, Track};
+
+pub fn info(project: &Project) {
+    let settings = &project.settings;
+    println!("Name:      {}", settings.name);
+    println!("Directory: {}", settings.directory);
+    println!("BPM:       {}", settings.bpm);
+    println!("Patterns:  {}", project.patterns.len());
+    for pattern in &project.patterns {
+        let audio = (0..8)
+            .filter(|&t| {
+                pattern
+                    .active_audio_track(t)
+                    .map(|t| t.steps.iter().any(|s| !s.is_empty()))
+                    .unwrap_or(false)
+            })
+            .count();
+        let midi = (0..8)
+            .filter(|&t| {
+                pattern
+                    .active_midi_track(t)
+                    .map(|t| t.steps.iter().any(|s| !s.is_empty()))
+                    .unwrap_or(false)
+            })
+            .count();
+        let variations = pattern
+            .audio_tracks
+            .iter()
+            .map(|v| v.iter().flatten().count())
+            .chain(
+                pattern
+                    .midi_tracks
+                    .iter()
+                    .map(|v| v.iter().flatten().count()),
+            )
+            .sum::<usize>();
+        println!(
+            "  Pattern {:>2}: {} audio and {} MIDI tracks with notes; {} track variations",
+            pattern.number, audio, midi, variations
+        );
+    }
+}
+
+fn cc_row(name: &str, cc: &CCMapping) {
+    println!(
+        "  {:<8} {:>6} {:>9} {:>6} {:>5} {:>6} {:>5} {:>9} {:>9}",
+        name,
+        cc.cutoff,
+        cc.resonance,
+        cc.sample_attack,
+        cc.sample_decay,
+        cc.reverb_send,
+        cc.delay_send,
+        cc.overdrive,
+        cc.bit_depth
+    );
+}
+
+pub fn settings(settings: &Settings) {
+    println!("Name:      {}", settings.name);
+    println!("Directory: {}", settings.directory);
+    println!("BPM:       {}", settings.bpm);
+    for (name, mappings) in [
+        ("Jack", &settings.jack_cc_mapping),
+        ("USB", &settings.usb_cc_mapping),
+    ] {
+        println!();
+        println!("{} MIDI CC mapping", name);
+        println!(
+            "  {:<8} {:>6} {:>9} {:>6} {:>5} {:>6} {:>5} {:>9} {:>9}",
+            "Channel",
+            "Cutoff",
+            "Resonance",
+            "Attack",
+            "Decay",
+            "Reverb",
+            "Delay",
+            "Overdrive",
+            "Bit depth"
+        );
+        for (i, cc) in mappings.iter().enumerate() {
+            cc_row(&(i + 1).to_string(), cc);
+        }
+    }
+}
+
+pub fn pattern(pattern: &Pattern) {
+    println!("Pattern {}", pattern.number);
+    for track in 0..8 {
+        println!();
+        match pattern.active_audio_track(track) {
+            Some(t) => audio_track(t),
+            None => println!("Audio track {}: no active variation", track + 1),
+        }
+    }
+    for track in 0..8 {
+        println!();
+        match pattern.active_midi_track(track) {
+            Some(t) => midi_track(t),
+            None => println!("MIDI track {}: no active variation", track + 1),
+        }
+    }
+}
+
+fn track_header<S: play_files::TrackStep + Clone>(kind: &str, track: &Track<S>) {
+    println!(
+        "{} track {} | variation {}{} | {} steps | {:?} | speed {} | swing {}%",
+        kind,
+        track.number + 1,
+        track.variation,
+        if track.is_default { " (active)" } else { "" },
+        track.steps.len(),
+        PlayMode::from(track.play_mode),
+        format_track_speed(track.track_speed),
+        track.swing
+    );
+}
+
+/// Sample start and end as a percentage of the sample's length
+fn sample_position(value: i16) -> String {
+    format!("{:.0}%", value as f32 / 32767.0 * 100.0)
+}
+
+pub fn audio_track(track: &Track<Step>) {
+    track_header("Audio", track);
+    if track.steps.iter().all(|s| s.is_empty()) {
+        println!("  (no notes)");
+        return;
+    }
+    println!(
+        "  {:>2} {:<4} {:>3} {:>7} {:>4} {:>6} {:>4} {:>5} {:>4} {:>4} {:>4} {:>5} {:>5} {:>4} {:>4} {:>5} {:>6}  {:<28} Chance",
+        "##", "Note", "Smp", "Volume", "Pan", "Filter", "Res", "Drive", "Bits", "Rev", "Dly",
+        "Start", "End", "Atk", "Dec", "Tune", "Move", "Repeat"
+    );
+    for step in track.steps.iter().filter(|s| !s.is_empty()) {
+        println!(
+            "  {:>2} {:<4} {:>3} {:>7} {:>4} {:>6} {:>4} {:>5} {:>4} {:>4} {:>4} {:>5} {:>5} {:>4} {:>4} {:>5} {:>6}  {:<28} {}",
+            step.number,
+            note_name(step.note),
+            step.sample,
+            format_volume(step.volume),
+            format_pan(step.pan),
+            format_filter(step.filter_cutoff),
+            format!("{}%", percent(step.filter_resonance as i32)),
+            format!("{}%", percent(step.overdrive as i32)),
+            step.bit_depth,
+            format!("{}%", percent(step.reverb as i32)),
+            format!("{}%", percent(step.delay as i32)),
+            sample_position(step.sample_start),
+            sample_position(step.sample_end),
+            format!("{}%", percent(step.sample_attack as i32)),
+            format!("{}%", percent(step.sample_decay as i32)),
+            format_cents(step.micro_tune),
+            format_micro_move(step.micro_move),
+            repeat(step.repeat_type, step.repeat_grid),
+            chance(step.chance_type, step.chance_action),
+        );
+    }
+}
+
+fn repeat(repeat_type: u16, repeat_grid: u16) -> String {
+    if repeat_type == 0 {
+        repeat_type_name(repeat_type)
+    } else {
+        format!(
+            "{} ({})",
+            repeat_type_name(repeat_type),
+            repeat_grid_name(repeat_grid)
+        )
+    }
+}
+
+fn chance(chance_type: u16, chance_action: u16) -> String {
+    if chance_type == 0 {
+        chance_type_name(chance_type)
+    } else {
+        format!(
+            "{} ({})",
+            chance_type_name(chance_type),
+            chance_action_name(chance_action)
+        )
+    }
+}
+
+fn ccs(step: &MidiStep) -> String {
+    let ccs = [
+        (12, step.cc12),
+        (13, step.cc13),
+        (17, step.cc17),
+        (19, step.cc19),
+        (22, step.cc22),
+        (71, step.cc71),
+        (74, step.cc74),
+        (75, step.cc75),
+    ];
+    let set: Vec<String> = ccs
+        .iter()
+        .filter_map(|(cc, v)| v.map(|v| format!("{}:{}", cc, v)))
+        .collect();
+    if set.is_empty() {
+        "-".to_string()
+    } else {
+        set.join(" ")
+    }
+}
+
+pub fn midi_track(track: &Track<MidiStep>) {
+    track_header("MIDI", track);
+    if track.steps.iter().all(|s| s.is_empty()) {
+        println!("  (no notes)");
+        return;
+    }
+    println!(
+        "  {:>2} {:<4} {:>3} {:<7} {:>4} {:>6} {:<10} {:>5} {:>6}  {:<28} {:<24} CCs",
+        "##",
+        "Note",
+        "Vel",
+        "Channel",
+        "Prog",
+        "Length",
+        "Chord",
+        "Bend",
+        "Move",
+        "Repeat",
+        "Chance"
+    );
+    for step in track.steps.iter().filter(|s| !s.is_empty()) {
+        println!(
+            "  {:>2} {:<4} {:>3} {:<7} {:>4} {:>6} {:<10} {:>5} {:>6}  {:<28} {:<24} {}",
+            step.number,
+            note_name(step.note),
+            step.velocity,
+            format_midi_channel(step.channel),
+            step.program
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            format!("{:.2}", note_length(step.note_length)),
+            chord_name(step.chord),
+            step.pitch_bend
+                .map(format_cents)
+                .unwrap_or_else(|| "-".to_string()),
+            format_micro_move(step.micro_move),
+            repeat(step.repeat_type, step.repeat_grid),
+            chance(step.chance_type, step.chance_action),
+            ccs(step),
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Creating projects from scratch, with the values the device uses for new projects.
+
+use arr_macro::arr;
+
+use crate::{
+    CCMapping, MidiChannel, MidiStep, Pattern, PlayMode, Project, Samples, Settings, Step, Track,
+    TrackAttrs, TrackSpeed, TrackStep,
+};
+
+/// The sample folders of a new project
+const SAMPLE_FOLDERS: [&str; 20] = [
+    "Bass",
+    "FX",
+    "HiHat",
+    "Keys Chords",
+    "Keys Oneshots",
+    "Kick",
+    "Percussion",
+    "Plucks Chords",
+    "Plucks Oneshots",
+    "Snare",
+    "Tom",
+    "Perc Glitch",
+    "Snare Digital",
+    "Tom Digital",
+    "Vinyl Static",
+    "",
+    "",
+    "",
+    "",
+    "",
+];
+
+/// Define `with_*` methods that set a field and return `self`
+macro_rules! with_fields {
+    ($($(#[$doc:meta])* $method:ident: $field:ident: $ty:ty),* $(,)?) => {
+        $(
+            $(#[$doc])*
+            pub fn $method(mut self, value: $ty) -> Self {
+                self.$field = value;
+                self
+            }
+        )*
+    };
+}
+
+impl Project {
+    /// A new project as created on the device: 120 bpm, the default CC mappings and sample
+    /// folders, and a first pattern of empty tracks
+    pub fn new(name: &str) -> Self {
+        Self {
+            settings: Settings {
+                name: name.to_string(),
+                directory: "/Projects".to_string(),
+                bpm: 120.0,
+                jack_cc_mapping: vec![CCMapping::default(); 16],
+                usb_cc_mapping: vec![CCMapping::default(); 16],
+                xa8: vec![0x01, 0x01],
+                ..Settings::default()
+            },
+            samples: Samples::default(),
+            patterns: vec![Pattern::new(0)],
+        }
+    }
+}
+
+impl Default for CCMapping {
+    fn default() -> Self {
+        Self {
+            u_first_bytes: [0x01, 0x0A, 0x0A, 0x08],
+            cutoff: 74,
+            resonance: 71,
+            sample_attack: 22,
+            sample_decay: 75,
+            reverb_send: 17,
+            delay_send: 19,
+            overdrive: 12,
+            bit_depth: 13,
+        }
+    }
+}
+
+impl Default for Samples {
+    /// Every slot in the first folder, with the device's default folders
+    fn default() -> Self {
+        Self {
+            slot_folders: vec![0; 256],
+            x10: 0,
+            folders: SAMPLE_FOLDERS.iter().map(|f| f.to_string()).collect(),
+            rest: vec![],
+        }
+    }
+}
+
+impl Pattern {
+    /// A pattern of empty tracks, where the first variation of each track is active
+    pub fn new(number: u8) -> Self {
+        let mut n = 0;
+        let audio_tracks = arr![{ n += 1; Self::new_variations(n - 1) }; 8];
+        let mut n = 0;
+        let midi_tracks = arr![{ n += 1; Self::new_variations(n - 1) }; 8];
+        let mut rest = vec![0x15];
+        rest.extend(120f32.to_le_bytes());
+        Self {
+            number,
+            audio_tracks,
+            midi_tracks,
+            rest,
+            active_track_files: vec![],
+        }
+    }
+
+    fn new_variations<S: TrackStep + Clone + Default>(number: usize) -> [Option<Track<S>>; 16] {
+        let mut variations: [Option<Track<S>>; 16] = Default::default();
+        variations[0] = Some(Track::new(number, 0).active());
+        variations
+    }
+
+    /// Put `track` in the slot of its number and variation. If it is active, the other
+    /// variations of the track are made inactive.
+    ///
+    /// # Panics
+    ///
+    /// If the track number is not 0-7 or the variation is not 0-15.
+    pub fn with_audio_track(mut self, track: Track<Step>) -> Self {
+        check_slot(&track);
+        Self::set_variation(&mut self.audio_tracks[track.number], track);
+        self
+    }
+
+    /// Put `track` in the slot of its number and variation. If it is active, the other
+    /// variations of the track are made inactive.
+    ///
+    /// # Panics
+    ///
+    /// If the track number is not 0-7 or the variation is not 0-15.
+    pub fn with_midi_track(mut self, track: Track<MidiStep>) -> Self {
+        check_slot(&track);
+        Self::set_variation(&mut self.midi_tracks[track.number], track);
+        self
+    }
+
+    fn set_variation<S: TrackStep + Clone>(
+        variations: &mut [Option<Track<S>>; 16],
+        track: Track<S>,
+    ) {
+        if track.is_default {
+            for other in variations.iter_mut().flatten() {
+                other.is_default = false;
+            }
+        }
+        let v = track.variation;
+        variations[v] = Some(track);
+    }
+}
+
+fn check_slot<S: TrackStep + Clone>(track: &Track<S>) {
+    assert!(
+        track.number < 8 && track.variation < 16,
+        "track {} variation {} is not a slot of a pattern, which has tracks 0-7 and variations 0-15",
+        track.number,
+        track.variation
+    );
+}
+
+impl<S: TrackStep + Clone + Default> Track<S> {
+    /// An inactive variation of 16 empty steps, with a swing of 50% and a speed of 1/1
+    pub fn new(number: usize, variation: usize) -> Self {
+        let mut track = Self {
+            number,
+            variation,
+            steps: vec![],
+            swing: 50,
+            play_mode: PlayMode::Forward.into(),
+            track_speed: TrackSpeed::Fraction(1, 1),
+            is_default: false,
+            attrs: TrackAttrs {
+                num_steps: 16,
+                swing: 50,
+                play_mode: 0,
+                track_speed: TrackSpeed::Fraction(1, 1),
+                variation: 0,
+                variations: vec![false; 16],
+                ux18: 4,
+            },
+        };
+        track.resize(16);
+        track
+    }
+
+    /// Change the number of steps, adding empty steps to the end or removing steps from it.
+    /// Tracks have 1 to 64 steps, so other numbers are clamped to that range.
+    pub fn with_num_steps(mut self, num_steps: usize) -> Self {
+        self.resize(num_steps.clamp(1, 64));
+        self
+    }
+
+    fn resize(&mut self, num_steps: usize) {
+        let len = self.steps.len();
+        self.steps.truncate(num_steps);
+        self.steps.extend((len..num_steps).map(|number| {
+            let mut step = S::default();
+            step.set_number(number);
+            step
+        }));
+    }
+
+    /// Make this the variation that plays
+    pub fn active(mut self) -> Self {
+        self.is_default = true;
+        self
+    }
+
+    /// Replace the step with the number of `step`.
+    ///
+    /// Panics if the track doesn't have that many steps.
+    pub fn with_step(mut self, step: S) -> Self {
+        let number = step.number();
+        assert!(
+            number < self.steps.len(),
+            "step {} is beyond the {} steps of the track",
+            number,
+            self.steps.len()
+        );
+        self.steps[number] = step;
+        self
+    }
+
+    with_fields! {
+        /// Percentage 25-75
+        with_swing: swing: u8,
+        with_track_speed: track_speed: TrackSpeed,
+    }
+
+    pub fn with_play_mode(mut self, play_mode: PlayMode) -> Self {
+        self.play_mode = play_mode.into();
+        self
+    }
+}
+
+impl Default for Step {
+    /// An empty step, as stored by the device
+    fn default() -> Self {
+        Self::baseline(0, None)
+    }
+}
+
+impl Step {
+    /// A step playing `note` (a midi note number, where 60 is C4) with the device's defaults:
+    /// sample 0 at 0dB, unfiltered and without effects
+    pub fn new_note(number: usize, note: u8) -> Self {
+        Self::baseline(number, Some(note))
+    }
+
+    with_fields! {
+        with_sample: sample: u16,
+        /// 0db at 7600; 200 = 1db
+        with_volume: volume: u16,
+        /// -10000 is hard L, 10000 is hard right; 100 = 1%
+        with_pan: pan: i16,
+        /// -10000 is LP100; 10000 is HP100; 100 = 1%
+        with_filter_cutoff: filter_cutoff: i16,
+        with_filter_resonance: filter_resonance: u16,
+        with_overdrive: overdrive: u16,
+        /// 4-16
+        with_bit_depth: bit_depth: u8,
+        with_reverb: reverb: i16,
+        with_delay: delay: i16,
+        /// 0-32767
+        with_sample_start: sample_start: i16,
+        /// 0-32767
+        with_sample_end: sample_end: i16,
+        with_sample_attack: sample_attack: u16,
+        with_sample_decay: sample_decay: u16,
+        /// 100 = 1 cent
+        with_micro_tune: micro_tune: i16,
+        /// -11 to 11
+        with_micro_move: micro_move: i16,
+        with_repeat_type: repeat_type: u16,
+        with_repeat_grid: repeat_grid: u16,
+        with_chance_type: chance_type: u16,
+        with_chance_action: chance_action: u16,
+    }
+}
+
+impl Default for MidiStep {
+    /// An empty step, as stored by the device
+    fn default() -> Self {
+        Self::baseline(0, None)
+    }
+}
+
+impl MidiStep {
+    /// A step playing `note` with the device's defaults: velocity 100 on Jack channel 1
+    pub fn new_note(number: usize, note: u8) -> Self {
+        Self::baseline(number, Some(note))
+    }
+
+    with_fields! {
+        with_channel: channel: MidiChannel,
+        with_velocity: velocity: u8,
+        with_note_length: note_length: u16,
+        with_chord: chord: i16,
+        /// -11 to 11
+        with_micro_move: micro_move: i16,
+        with_chance_type: chance_type: u16,
+        with_chance_action: chance_action: u16,
+    }
+
+    /// Set the bits that record which optional values a note sends
+    fn update_rest(mut self) -> Self {
+        if self.rest.len() > 7 {
+            let rest = self.note_rest();
+            self.rest[5..8].copy_from_slice(&rest[5..8]);
+        }
+        self
+    }
+
+    pub fn with_repeat_type(mut self, repeat_type: u16) -> Self {
+        self.repeat_type = repeat_type;
+        self.update_rest()
+    }
+
+    pub fn with_repeat_grid(mut self, repeat_grid: u16) -> Self {
+        self.repeat_grid = repeat_grid;
+        self.update_rest()
+    }
+
+    pub fn with_program(mut self, program: u8) -> Self {
+        self.program = Some(program);
+        self.update_rest()
+    }
+
+    /// 100 = 1 cent
+    pub fn with_pitch_bend(mut self, pitch_bend: i16) -> Self {
+        self.pitch_bend = Some(pitch_bend);
+        self.update_rest()
+    }
+
+    /// Send `value` on one of the CCs a step can send: 12, 13, 17, 19, 22, 71, 74 or 75.
+    ///
+    /// Panics for other CCs.
+    pub fn with_cc(mut self, cc: u8, value: u8) -> Self {
+        let field = self
+            .cc_mut(cc)
+            .unwrap_or_else(|| panic!("A step can't send CC{}", cc));
+        *field = Some(value);
+        self.update_rest()
+    }
+}
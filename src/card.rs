@@ -0,0 +1,205 @@
+//! Finding the projects and samples on a Play SD card, or a backup of one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::vfs::{self, Vfs};
+use crate::{LazyProject, ParseError, Project, Result, Settings};
+
+/// The root directory of an SD card, which holds the `Projects` directory and the `Samples`
+/// library
+#[derive(PartialEq, Clone, Debug)]
+pub struct SdCard {
+    pub path: PathBuf,
+}
+
+/// A project found on an SD card, with the details that can be read from its settings
+#[derive(PartialEq, Clone, Debug)]
+pub struct ProjectEntry {
+    /// The project directory
+    pub path: PathBuf,
+    pub name: String,
+    pub bpm: f32,
+    /// When any of the project's files was last modified
+    pub modified: SystemTime,
+}
+
+/// The samples on an SD card, which projects copy their samples from
+#[derive(PartialEq, Clone, Debug)]
+pub struct SampleLibrary {
+    pub path: PathBuf,
+    pub folders: Vec<SampleFolder>,
+}
+
+/// A directory of the sample library that contains samples
+#[derive(PartialEq, Clone, Debug)]
+pub struct SampleFolder {
+    /// The path of the folder within the library, e.g. `Drums/Kick`. The samples at the top of
+    /// the library are in the folder named ``.
+    pub name: String,
+    pub samples: Vec<PathBuf>,
+}
+
+// Projects and sample folders are found by listing directories, which [Vfs::list_dir] leaves
+// out, so only the files of a project are read through it.
+
+/// The entry of `dir` named `name`, ignoring case as FAT file systems do
+fn find_dir(dir: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_dir()
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.eq_ignore_ascii_case(name))
+        })
+}
+
+fn sorted_entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|_| ParseError(format!("Cannot read directory: {:?}", dir)))?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl SdCard {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// The directory holding the projects
+    pub fn projects_dir(&self) -> Option<PathBuf> {
+        find_dir(&self.path, "Projects")
+    }
+
+    /// Every directory in `Projects` that has a settings file, sorted by directory name, with
+    /// its entry or the error reading it, so that one damaged project doesn't hide the others.
+    /// Patterns are not read.
+    pub fn projects(&self) -> Result<Vec<(PathBuf, Result<ProjectEntry>)>> {
+        let dir = self
+            .projects_dir()
+            .ok_or_else(|| ParseError(format!("No Projects directory in {:?}", &self.path)))?;
+        Ok(sorted_entries(&dir)?
+            .into_iter()
+            .filter(|path| {
+                path.list_dir("")
+                    .is_ok_and(|names| names.iter().any(|n| n == "settings"))
+            })
+            .map(|path| {
+                let entry = ProjectEntry::read(&path);
+                (path, entry)
+            })
+            .collect())
+    }
+
+    /// The `Samples` library, if the card has one
+    pub fn sample_library(&self) -> Result<Option<SampleLibrary>> {
+        find_dir(&self.path, "Samples")
+            .map(|path| SampleLibrary::read(&path))
+            .transpose()
+    }
+}
+
+impl ProjectEntry {
+    /// Read the settings of the project in the directory `path`
+    pub fn read(path: &Path) -> Result<Self> {
+        let settings = vfs::in_file(
+            Settings::from_bytes(&vfs::read_file(path, "settings")?),
+            "settings",
+        )?;
+        let mut files = vec![path.join("settings"), path.join("samples")];
+        let patterns = vfs::list_dir(path, "patterns").unwrap_or_default();
+        files.extend(patterns.iter().map(|name| path.join("patterns").join(name)));
+        let modified = files
+            .iter()
+            .filter_map(|f| modified(f))
+            .max()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        Ok(Self {
+            path: path.to_path_buf(),
+            name: settings.name,
+            bpm: settings.bpm,
+            modified,
+        })
+    }
+
+    /// Read the whole project
+    pub fn load(&self) -> Result<Project> {
+        Project::read(&self.path)
+    }
+
+    /// Open the project to read its patterns as they are needed
+    pub fn open(&self) -> Result<LazyProject> {
+        LazyProject::open(&self.path)
+    }
+}
+
+impl SampleLibrary {
+    /// Find the `.wav` files in the directory `path` and its subdirectories
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut library = Self {
+            path: path.to_path_buf(),
+            folders: vec![],
+        };
+        library.read_folder(path, "")?;
+        Ok(library)
+    }
+
+    fn read_folder(&mut self, dir: &Path, name: &str) -> Result<()> {
+        let mut samples = vec![];
+        let mut subdirs = vec![];
+        for path in sorted_entries(dir)? {
+            let file_name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default()
+                .to_string();
+            // Skip hidden files, such as those macOS leaves on cards
+            if file_name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                subdirs.push((path, file_name));
+            } else if path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("wav"))
+            {
+                samples.push(path);
+            }
+        }
+        if !samples.is_empty() {
+            self.folders.push(SampleFolder {
+                name: name.to_string(),
+                samples,
+            });
+        }
+        for (path, file_name) in subdirs {
+            let name = if name.is_empty() {
+                file_name
+            } else {
+                format!("{}/{}", name, file_name)
+            };
+            self.read_folder(&path, &name)?;
+        }
+        Ok(())
+    }
+
+    /// Get a folder by its name
+    pub fn folder(&self, name: &str) -> Option<&SampleFolder> {
+        self.folders.iter().find(|f| f.name == name)
+    }
+}
@@ -0,0 +1,455 @@
+//! Semantic comparison of two projects.
+
+use std::fmt;
+
+use crate::units::*;
+use crate::{CCMapping, MidiStep, Pattern, PlayMode, Project, Step, Track, TrackStep};
+
+/// A difference between two projects
+#[derive(PartialEq, Clone, Debug)]
+pub enum Change {
+    /// Something, such as a pattern or a track variation, only exists in the second project
+    Added(String),
+    /// Something only exists in the first project
+    Removed(String),
+    /// A value differs. Values are formatted in the units shown on the device.
+    Changed {
+        /// E.g. `pattern 3 audio track 2 variation 1 step 7`. Tracks and variations are
+        /// numbered from 0, as in the names of track files.
+        location: String,
+        field: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(location) => write!(f, "{}: added", location),
+            Change::Removed(location) => write!(f, "{}: removed", location),
+            Change::Changed {
+                location,
+                field,
+                old,
+                new,
+            } => write!(f, "{}: {} {} → {}", location, field, old, new),
+        }
+    }
+}
+
+/// The changes that turn project `a` into project `b`. Undecoded bytes are not compared.
+pub fn diff(a: &Project, b: &Project) -> Vec<Change> {
+    let mut differ = Differ::default();
+    differ.project(a, b);
+    differ.changes
+}
+
+// The formatters of the values that are shared with merge conflicts
+
+pub(crate) fn percent_of(value: impl Into<i64>) -> String {
+    format!("{}%", percent(value.into() as i32))
+}
+
+pub(crate) fn option<T: fmt::Display>(value: Option<T>) -> String {
+    value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub(crate) fn format_sample(sample: u16) -> String {
+    format!("{:03}", sample)
+}
+
+pub(crate) fn format_swing(swing: u8) -> String {
+    format!("{}%", swing)
+}
+
+pub(crate) fn format_play_mode(play_mode: u8) -> String {
+    format!("{:?}", PlayMode::from(play_mode))
+}
+
+pub(crate) fn format_note_length(length: u16) -> String {
+    format!("{:.2}", note_length(length))
+}
+
+pub(crate) fn format_pitch_bend(pitch_bend: Option<i16>) -> String {
+    pitch_bend
+        .map(format_cents)
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub(crate) fn format_cc(cc: u8) -> String {
+    format!("CC{}", cc)
+}
+
+/// The text notation of a step without its number, which is part of the location
+pub(crate) fn step_text<S: TrackStep + fmt::Display>(step: &S) -> String {
+    if step.is_empty() {
+        return "---".to_string();
+    }
+    let text = step.to_string();
+    match text.split_once(' ') {
+        Some((_, rest)) => rest.to_string(),
+        None => text,
+    }
+}
+
+#[derive(Default)]
+struct Differ {
+    changes: Vec<Change>,
+}
+
+impl Differ {
+    fn field<T: PartialEq>(
+        &mut self,
+        location: &str,
+        field: &str,
+        a: T,
+        b: T,
+        format: impl Fn(T) -> String,
+    ) {
+        if a != b {
+            self.changes.push(Change::Changed {
+                location: location.to_string(),
+                field: field.to_string(),
+                old: format(a),
+                new: format(b),
+            });
+        }
+    }
+
+    /// Removed or Added for each entry past the end of the shorter of two lists
+    fn extra<T>(&mut self, a: &[T], b: &[T], location: impl Fn(usize) -> String) {
+        for i in b.len()..a.len() {
+            self.changes.push(Change::Removed(location(i)));
+        }
+        for i in a.len()..b.len() {
+            self.changes.push(Change::Added(location(i)));
+        }
+    }
+
+    fn project(&mut self, a: &Project, b: &Project) {
+        let (sa, sb) = (&a.settings, &b.settings);
+        self.field("settings", "name", &sa.name, &sb.name, |s| s.clone());
+        self.field("settings", "directory", &sa.directory, &sb.directory, |s| {
+            s.clone()
+        });
+        self.field("settings", "bpm", sa.bpm, sb.bpm, |bpm| bpm.to_string());
+        for (kind, ma, mb) in [
+            ("Jack", &sa.jack_cc_mapping, &sb.jack_cc_mapping),
+            ("USB", &sa.usb_cc_mapping, &sb.usb_cc_mapping),
+        ] {
+            let location = |i: usize| format!("CC mapping for {} {}", kind, i + 1);
+            for (i, (ma, mb)) in ma.iter().zip(mb).enumerate() {
+                self.cc_mapping(&location(i), ma, mb);
+            }
+            self.extra(ma, mb, location);
+        }
+
+        let (sa, sb) = (&a.samples, &b.samples);
+        let location = |i: usize| format!("sample folder {}", i);
+        for (i, (fa, fb)) in sa.folders.iter().zip(&sb.folders).enumerate() {
+            self.field(&location(i), "name", fa, fb, |f| f.clone());
+        }
+        self.extra(&sa.folders, &sb.folders, location);
+        for slot in 0..sa.slot_folders.len().min(sb.slot_folders.len()) {
+            let folder = |s: &crate::Samples| {
+                s.folder_name(slot)
+                    .map(|f| f.to_string())
+                    .unwrap_or_else(|| format!("#{}", s.slot_folders[slot]))
+            };
+            self.field(
+                &format!("sample slot {}", slot),
+                "folder",
+                folder(sa),
+                folder(sb),
+                |f| f,
+            );
+        }
+        self.extra(&sa.slot_folders, &sb.slot_folders, |slot| {
+            format!("sample slot {}", slot)
+        });
+
+        for pa in &a.patterns {
+            match b.pattern(pa.number) {
+                Some(pb) => self.pattern(pa, pb),
+                None => self
+                    .changes
+                    .push(Change::Removed(format!("pattern {}", pa.number))),
+            }
+        }
+        for pb in &b.patterns {
+            if a.pattern(pb.number).is_none() {
+                self.changes
+                    .push(Change::Added(format!("pattern {}", pb.number)));
+            }
+        }
+    }
+
+    fn cc_mapping(&mut self, location: &str, a: &CCMapping, b: &CCMapping) {
+        let fields = [
+            ("cutoff", a.cutoff, b.cutoff),
+            ("resonance", a.resonance, b.resonance),
+            ("sample_attack", a.sample_attack, b.sample_attack),
+            ("sample_decay", a.sample_decay, b.sample_decay),
+            ("reverb_send", a.reverb_send, b.reverb_send),
+            ("delay_send", a.delay_send, b.delay_send),
+            ("overdrive", a.overdrive, b.overdrive),
+            ("bit_depth", a.bit_depth, b.bit_depth),
+        ];
+        for (field, a, b) in fields {
+            self.field(location, field, a, b, format_cc);
+        }
+    }
+
+    fn pattern(&mut self, a: &Pattern, b: &Pattern) {
+        let location = format!("pattern {}", a.number);
+        self.variations(
+            &location,
+            "audio",
+            &a.audio_tracks,
+            &b.audio_tracks,
+            Self::step,
+        );
+        self.variations(
+            &location,
+            "MIDI",
+            &a.midi_tracks,
+            &b.midi_tracks,
+            Self::midi_step,
+        );
+    }
+
+    fn variations<S: TrackStep + Clone + fmt::Display>(
+        &mut self,
+        location: &str,
+        kind: &str,
+        a: &[[Option<Track<S>>; 16]; 8],
+        b: &[[Option<Track<S>>; 16]; 8],
+        diff_step: fn(&mut Self, &str, &S, &S),
+    ) {
+        for (n, (va, vb)) in a.iter().zip(b).enumerate() {
+            let location = format!("{} {} track {}", location, kind, n);
+            let active = |v: &[Option<Track<S>>; 16]| {
+                option(
+                    v.iter()
+                        .flatten()
+                        .find(|t| t.is_default)
+                        .map(|t| t.variation),
+                )
+            };
+            self.field(&location, "active variation", active(va), active(vb), |v| v);
+
+            for (v, (ta, tb)) in va.iter().zip(vb).enumerate() {
+                let location = format!("{} variation {}", location, v);
+                match (ta, tb) {
+                    (Some(ta), Some(tb)) => self.track(&location, ta, tb, diff_step),
+                    (Some(_), None) => self.changes.push(Change::Removed(location)),
+                    (None, Some(_)) => self.changes.push(Change::Added(location)),
+                    (None, None) => (),
+                }
+            }
+        }
+    }
+
+    fn track<S: TrackStep + Clone + fmt::Display>(
+        &mut self,
+        location: &str,
+        a: &Track<S>,
+        b: &Track<S>,
+        diff_step: fn(&mut Self, &str, &S, &S),
+    ) {
+        let len = |t: &Track<S>| t.steps.len();
+        self.field(location, "num_steps", len(a), len(b), |n| n.to_string());
+        self.field(location, "swing", a.swing, b.swing, format_swing);
+        self.field(
+            location,
+            "play_mode",
+            a.play_mode,
+            b.play_mode,
+            format_play_mode,
+        );
+        self.field(
+            location,
+            "track_speed",
+            a.track_speed,
+            b.track_speed,
+            format_track_speed,
+        );
+        for i in 0..len(a).max(len(b)) {
+            let location = format!("{} step {}", location, i);
+            match (a.steps.get(i), b.steps.get(i)) {
+                (Some(sa), Some(sb)) => diff_step(self, &location, sa, sb),
+                // Steps past the end of a track are empty on the device
+                (Some(s), None) => self.field(&location, "step", step_text(s), "---".into(), |s| s),
+                (None, Some(s)) => self.field(&location, "step", "---".into(), step_text(s), |s| s),
+                (None, None) => (),
+            }
+        }
+    }
+
+    fn step(&mut self, location: &str, a: &Step, b: &Step) {
+        if a.is_empty() || b.is_empty() {
+            // A note was added or removed. The values of empty steps aren't used.
+            if a.is_empty() != b.is_empty() {
+                self.field(location, "step", step_text(a), step_text(b), |s| s);
+            }
+            return;
+        }
+        let l = location;
+        self.field(l, "note", a.note, b.note, note_name);
+        self.field(l, "sample", a.sample, b.sample, format_sample);
+        self.field(l, "volume", a.volume, b.volume, format_volume);
+        self.field(l, "pan", a.pan, b.pan, format_pan);
+        self.field(
+            l,
+            "filter_cutoff",
+            a.filter_cutoff,
+            b.filter_cutoff,
+            format_filter,
+        );
+        let percents = [
+            (
+                "filter_resonance",
+                a.filter_resonance as i64,
+                b.filter_resonance as i64,
+            ),
+            ("overdrive", a.overdrive as i64, b.overdrive as i64),
+            ("reverb", a.reverb as i64, b.reverb as i64),
+            ("delay", a.delay as i64, b.delay as i64),
+            (
+                "sample_attack",
+                a.sample_attack as i64,
+                b.sample_attack as i64,
+            ),
+            ("sample_decay", a.sample_decay as i64, b.sample_decay as i64),
+        ];
+        for (field, a, b) in percents {
+            self.field(l, field, a, b, percent_of);
+        }
+        self.field(l, "bit_depth", a.bit_depth, b.bit_depth, |b| b.to_string());
+        self.field(l, "sample_start", a.sample_start, b.sample_start, |s| {
+            s.to_string()
+        });
+        self.field(l, "sample_end", a.sample_end, b.sample_end, |s| {
+            s.to_string()
+        });
+        self.field(l, "micro_tune", a.micro_tune, b.micro_tune, format_cents);
+        self.field(
+            l,
+            "micro_move",
+            a.micro_move,
+            b.micro_move,
+            format_micro_move,
+        );
+        self.field(l, "sample_folder", a.sample_folder, b.sample_folder, |f| {
+            f.to_string()
+        });
+        self.field(
+            l,
+            "repeat_type",
+            a.repeat_type,
+            b.repeat_type,
+            repeat_type_name,
+        );
+        self.field(
+            l,
+            "repeat_grid",
+            a.repeat_grid,
+            b.repeat_grid,
+            repeat_grid_name,
+        );
+        self.field(
+            l,
+            "chance_type",
+            a.chance_type,
+            b.chance_type,
+            chance_type_name,
+        );
+        self.field(
+            l,
+            "chance_action",
+            a.chance_action,
+            b.chance_action,
+            chance_action_name,
+        );
+    }
+
+    fn midi_step(&mut self, location: &str, a: &MidiStep, b: &MidiStep) {
+        if a.is_empty() || b.is_empty() {
+            if a.is_empty() != b.is_empty() {
+                self.field(location, "step", step_text(a), step_text(b), |s| s);
+            }
+            return;
+        }
+        let l = location;
+        self.field(l, "note", a.note, b.note, note_name);
+        self.field(l, "velocity", a.velocity, b.velocity, |v| v.to_string());
+        self.field(l, "channel", a.channel, b.channel, format_midi_channel);
+        self.field(l, "program", a.program, b.program, option);
+        self.field(
+            l,
+            "note_length",
+            a.note_length,
+            b.note_length,
+            format_note_length,
+        );
+        self.field(l, "chord", a.chord, b.chord, chord_name);
+        self.field(
+            l,
+            "pitch_bend",
+            a.pitch_bend,
+            b.pitch_bend,
+            format_pitch_bend,
+        );
+        let ccs = [
+            ("cc12", a.cc12, b.cc12),
+            ("cc13", a.cc13, b.cc13),
+            ("cc17", a.cc17, b.cc17),
+            ("cc19", a.cc19, b.cc19),
+            ("cc22", a.cc22, b.cc22),
+            ("cc71", a.cc71, b.cc71),
+            ("cc74", a.cc74, b.cc74),
+            ("cc75", a.cc75, b.cc75),
+        ];
+        for (field, a, b) in ccs {
+            self.field(l, field, a, b, option);
+        }
+        self.field(
+            l,
+            "micro_move",
+            a.micro_move,
+            b.micro_move,
+            format_micro_move,
+        );
+        self.field(
+            l,
+            "repeat_type",
+            a.repeat_type,
+            b.repeat_type,
+            repeat_type_name,
+        );
+        self.field(
+            l,
+            "repeat_grid",
+            a.repeat_grid,
+            b.repeat_grid,
+            repeat_grid_name,
+        );
+        self.field(
+            l,
+            "chance_type",
+            a.chance_type,
+            b.chance_type,
+            chance_type_name,
+        );
+        self.field(
+            l,
+            "chance_action",
+            a.chance_action,
+            b.chance_action,
+            chance_action_name,
+        );
+    }
+}
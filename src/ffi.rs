@@ -0,0 +1,406 @@
+//! A C interface, for using the parser from other languages. The header is
+//! `include/play_files.h`, generated with
+//! `cbindgen --config cbindgen.toml --output include/play_files.h`.
+//!
+//! Projects are opened with `pf_project_open` and freed with `pf_project_free`. Patterns,
+//! tracks and steps are borrowed from their project, and are valid until it is freed. Functions
+//! that can fail return NULL, and the reason can be read with `pf_last_error`.
+//!
+//! Accessors return 0 (or NULL) when given NULL, and values are converted to the units shown
+//! on the device.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use crate::units::{note_length, percent, volume_db};
+use crate::{MidiChannel, MidiStep, Pattern, Project, Step, Track, TrackSpeed};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// The message of the last error on this thread, or NULL if there hasn't been one. Valid until
+/// the next call that fails.
+#[no_mangle]
+pub extern "C" fn pf_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// A project opened with `pf_project_open`
+pub struct PfProject {
+    project: Project,
+    name: CString,
+}
+
+/// Read the project in the directory `path`. Returns NULL if it can't be read.
+///
+/// # Safety
+///
+/// `path` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pf_project_open(path: *const c_char) -> Option<Box<PfProject>> {
+    if path.is_null() {
+        set_error("No path given".to_string());
+        return None;
+    }
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        set_error("Path is not UTF-8".to_string());
+        return None;
+    };
+    // Malformed files are parse errors. A panic must still not unwind into C, so any that is
+    // left is caught as a last resort.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| Project::read(Path::new(path))));
+    match result {
+        Ok(Ok(project)) => Some(Box::new(PfProject {
+            name: CString::new(project.settings.name.replace('\0', " ")).unwrap_or_default(),
+            project,
+        })),
+        Ok(Err(e)) => {
+            set_error(e.to_string());
+            None
+        }
+        Err(_) => {
+            set_error(format!("Cannot parse project {:?}", path));
+            None
+        }
+    }
+}
+
+/// Free a project, and everything borrowed from it
+#[no_mangle]
+pub extern "C" fn pf_project_free(project: Option<Box<PfProject>>) {
+    drop(project);
+}
+
+/// The name of the project. Valid until the project is freed.
+#[no_mangle]
+pub extern "C" fn pf_project_name(project: Option<&PfProject>) -> *const c_char {
+    project.map_or(ptr::null(), |p| p.name.as_ptr())
+}
+
+/// Beats per minute
+#[no_mangle]
+pub extern "C" fn pf_project_bpm(project: Option<&PfProject>) -> f32 {
+    project.map_or(0.0, |p| p.project.settings.bpm)
+}
+
+#[no_mangle]
+pub extern "C" fn pf_project_pattern_count(project: Option<&PfProject>) -> usize {
+    project.map_or(0, |p| p.project.patterns.len())
+}
+
+/// The pattern at `index`, from 0 to `pf_project_pattern_count` - 1. Patterns are in the order
+/// of their files, which isn't always the order of their numbers.
+#[no_mangle]
+pub extern "C" fn pf_project_pattern(project: Option<&PfProject>, index: usize) -> *const Pattern {
+    project
+        .and_then(|p| p.project.patterns.get(index))
+        .map_or(ptr::null(), |p| p)
+}
+
+/// The number of the pattern, from 0
+#[no_mangle]
+pub extern "C" fn pf_pattern_number(pattern: Option<&Pattern>) -> u8 {
+    pattern.map_or(0, |p| p.number)
+}
+
+/// The variation of audio track `track` (0-7) that was active when the pattern was saved
+#[no_mangle]
+pub extern "C" fn pf_pattern_audio_track(
+    pattern: Option<&Pattern>,
+    track: usize,
+) -> *const Track<Step> {
+    pattern
+        .filter(|_| track < 8)
+        .and_then(|p| p.active_audio_track(track))
+        .map_or(ptr::null(), |t| t)
+}
+
+/// Variation `variation` (0-15) of audio track `track` (0-7), or NULL if the pattern doesn't
+/// have it
+#[no_mangle]
+pub extern "C" fn pf_pattern_audio_variation(
+    pattern: Option<&Pattern>,
+    track: usize,
+    variation: usize,
+) -> *const Track<Step> {
+    pattern
+        .and_then(|p| p.audio_tracks.get(track)?.get(variation)?.as_ref())
+        .map_or(ptr::null(), |t| t)
+}
+
+/// The variation of MIDI track `track` (0-7) that was active when the pattern was saved
+#[no_mangle]
+pub extern "C" fn pf_pattern_midi_track(
+    pattern: Option<&Pattern>,
+    track: usize,
+) -> *const Track<MidiStep> {
+    pattern
+        .filter(|_| track < 8)
+        .and_then(|p| p.active_midi_track(track))
+        .map_or(ptr::null(), |t| t)
+}
+
+/// Variation `variation` (0-15) of MIDI track `track` (0-7), or NULL if the pattern doesn't
+/// have it
+#[no_mangle]
+pub extern "C" fn pf_pattern_midi_variation(
+    pattern: Option<&Pattern>,
+    track: usize,
+    variation: usize,
+) -> *const Track<MidiStep> {
+    pattern
+        .and_then(|p| p.midi_tracks.get(track)?.get(variation)?.as_ref())
+        .map_or(ptr::null(), |t| t)
+}
+
+/// The number of steps of the track, from 1 to 64
+#[no_mangle]
+pub extern "C" fn pf_track_step_count(track: Option<&Track<Step>>) -> usize {
+    track.map_or(0, |t| t.steps.len())
+}
+
+/// The step at `index`, from 0 to `pf_track_step_count` - 1
+#[no_mangle]
+pub extern "C" fn pf_track_step(track: Option<&Track<Step>>, index: usize) -> *const Step {
+    track
+        .and_then(|t| t.steps.get(index))
+        .map_or(ptr::null(), |s| s)
+}
+
+#[no_mangle]
+pub extern "C" fn pf_track_variation(track: Option<&Track<Step>>) -> usize {
+    track.map_or(0, |t| t.variation)
+}
+
+/// Swing in percent, 25-75
+#[no_mangle]
+pub extern "C" fn pf_track_swing(track: Option<&Track<Step>>) -> u8 {
+    track.map_or(0, |t| t.swing)
+}
+
+/// Steps per beat, relative to the project's: e.g. 2.0 for 2/1. 0 when paused.
+#[no_mangle]
+pub extern "C" fn pf_track_speed(track: Option<&Track<Step>>) -> f32 {
+    track.map_or(0.0, |t| speed(t.track_speed))
+}
+
+fn speed(track_speed: TrackSpeed) -> f32 {
+    match track_speed {
+        TrackSpeed::Fraction(n, d) if d > 0 => n as f32 / d as f32,
+        _ => 0.0,
+    }
+}
+
+/// The number of steps of the track, from 1 to 64
+#[no_mangle]
+pub extern "C" fn pf_midi_track_step_count(track: Option<&Track<MidiStep>>) -> usize {
+    track.map_or(0, |t| t.steps.len())
+}
+
+/// The step at `index`, from 0 to `pf_midi_track_step_count` - 1
+#[no_mangle]
+pub extern "C" fn pf_midi_track_step(
+    track: Option<&Track<MidiStep>>,
+    index: usize,
+) -> *const MidiStep {
+    track
+        .and_then(|t| t.steps.get(index))
+        .map_or(ptr::null(), |s| s)
+}
+
+#[no_mangle]
+pub extern "C" fn pf_midi_track_variation(track: Option<&Track<MidiStep>>) -> usize {
+    track.map_or(0, |t| t.variation)
+}
+
+/// Swing in percent, 25-75
+#[no_mangle]
+pub extern "C" fn pf_midi_track_swing(track: Option<&Track<MidiStep>>) -> u8 {
+    track.map_or(0, |t| t.swing)
+}
+
+/// Steps per beat, relative to the project's: e.g. 2.0 for 2/1. 0 when paused.
+#[no_mangle]
+pub extern "C" fn pf_midi_track_speed(track: Option<&Track<MidiStep>>) -> f32 {
+    track.map_or(0.0, |t| speed(t.track_speed))
+}
+
+/// Whether there is no note on the step
+#[no_mangle]
+pub extern "C" fn pf_step_is_empty(step: Option<&Step>) -> bool {
+    step.is_none_or(|s| s.is_empty())
+}
+
+/// The sample slot, from 0
+#[no_mangle]
+pub extern "C" fn pf_step_sample(step: Option<&Step>) -> u16 {
+    step.map_or(0, |s| s.sample)
+}
+
+/// MIDI note number, where 60 is C4
+#[no_mangle]
+pub extern "C" fn pf_step_note(step: Option<&Step>) -> u8 {
+    step.map_or(0, |s| s.note)
+}
+
+/// Volume in dB: -INFINITY when silent
+#[no_mangle]
+pub extern "C" fn pf_step_volume_db(step: Option<&Step>) -> f32 {
+    step.and_then(|s| volume_db(s.volume))
+        .unwrap_or(f32::NEG_INFINITY)
+}
+
+/// Pan in percent, from -100 (hard left) to 100 (hard right)
+#[no_mangle]
+pub extern "C" fn pf_step_pan(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.pan as i32))
+}
+
+/// Filter cutoff in percent, from -100 (LP100) to 100 (HP100). 0 is off.
+#[no_mangle]
+pub extern "C" fn pf_step_filter_cutoff(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.filter_cutoff as i32))
+}
+
+/// Filter resonance in percent
+#[no_mangle]
+pub extern "C" fn pf_step_filter_resonance(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.filter_resonance as i32))
+}
+
+/// Overdrive in percent
+#[no_mangle]
+pub extern "C" fn pf_step_overdrive(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.overdrive as i32))
+}
+
+/// Bit depth, 4-16
+#[no_mangle]
+pub extern "C" fn pf_step_bit_depth(step: Option<&Step>) -> u8 {
+    step.map_or(0, |s| s.bit_depth)
+}
+
+/// Micro move in 24ths of a step, from -11 to 11
+#[no_mangle]
+pub extern "C" fn pf_step_micro_move(step: Option<&Step>) -> i16 {
+    step.map_or(0, |s| s.micro_move)
+}
+
+/// Micro tune in cents, from -100 to 100
+#[no_mangle]
+pub extern "C" fn pf_step_micro_tune(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.micro_tune as i32))
+}
+
+/// Reverb send in percent
+#[no_mangle]
+pub extern "C" fn pf_step_reverb(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.reverb as i32))
+}
+
+/// Delay send in percent
+#[no_mangle]
+pub extern "C" fn pf_step_delay(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.delay as i32))
+}
+
+/// Where the sample starts playing, from 0.0 (its start) to 1.0 (its end)
+#[no_mangle]
+pub extern "C" fn pf_step_sample_start(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| s.sample_start as f32 / i16::MAX as f32)
+}
+
+/// Where the sample stops playing, from 0.0 (its start) to 1.0 (its end)
+#[no_mangle]
+pub extern "C" fn pf_step_sample_end(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| s.sample_end as f32 / i16::MAX as f32)
+}
+
+/// Sample attack in percent
+#[no_mangle]
+pub extern "C" fn pf_step_sample_attack(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.sample_attack as i32))
+}
+
+/// Sample decay in percent
+#[no_mangle]
+pub extern "C" fn pf_step_sample_decay(step: Option<&Step>) -> f32 {
+    step.map_or(0.0, |s| percent(s.sample_decay as i32))
+}
+
+/// The raw chance type: 0 is always
+#[no_mangle]
+pub extern "C" fn pf_step_chance_type(step: Option<&Step>) -> u16 {
+    step.map_or(0, |s| s.chance_type)
+}
+
+/// The raw repeat type: 0 is off
+#[no_mangle]
+pub extern "C" fn pf_step_repeat_type(step: Option<&Step>) -> u16 {
+    step.map_or(0, |s| s.repeat_type)
+}
+
+/// Whether there is no note on the step
+#[no_mangle]
+pub extern "C" fn pf_midi_step_is_empty(step: Option<&MidiStep>) -> bool {
+    step.is_none_or(|s| s.is_empty())
+}
+
+/// MIDI note number, where 60 is C4
+#[no_mangle]
+pub extern "C" fn pf_midi_step_note(step: Option<&MidiStep>) -> u8 {
+    step.map_or(0, |s| s.note)
+}
+
+#[no_mangle]
+pub extern "C" fn pf_midi_step_velocity(step: Option<&MidiStep>) -> u8 {
+    step.map_or(0, |s| s.velocity)
+}
+
+/// MIDI channel, from 1 to 16, on the jack or USB as given by `pf_midi_step_is_usb`
+#[no_mangle]
+pub extern "C" fn pf_midi_step_channel(step: Option<&MidiStep>) -> u8 {
+    step.map_or(0, |s| match s.channel {
+        MidiChannel::Jack(n) | MidiChannel::Usb(n) => n,
+    })
+}
+
+/// Whether the step is sent over USB rather than the MIDI jack
+#[no_mangle]
+pub extern "C" fn pf_midi_step_is_usb(step: Option<&MidiStep>) -> bool {
+    step.is_some_and(|s| matches!(s.channel, MidiChannel::Usb(_)))
+}
+
+/// Note length as shown on the device
+#[no_mangle]
+pub extern "C" fn pf_midi_step_note_length(step: Option<&MidiStep>) -> f32 {
+    step.map_or(0.0, |s| note_length(s.note_length))
+}
+
+/// Micro move in 24ths of a step, from -11 to 11
+#[no_mangle]
+pub extern "C" fn pf_midi_step_micro_move(step: Option<&MidiStep>) -> i16 {
+    step.map_or(0, |s| s.micro_move)
+}
+
+/// Program change, or -1 if the step doesn't send one
+#[no_mangle]
+pub extern "C" fn pf_midi_step_program(step: Option<&MidiStep>) -> i16 {
+    step.and_then(|s| s.program).map_or(-1, |p| p as i16)
+}
+
+/// Pitch bend in cents, or 0 if the step doesn't send one
+#[no_mangle]
+pub extern "C" fn pf_midi_step_pitch_bend(step: Option<&MidiStep>) -> f32 {
+    step.and_then(|s| s.pitch_bend)
+        .map_or(0.0, |p| percent(p as i32))
+}
@@ -0,0 +1,101 @@
+//! Reading the patterns of a project only when they are needed.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::{ParseError, Pattern, Project, Result, Samples, Settings};
+
+/// A project whose settings are read when it is opened, and whose samples metadata and
+/// patterns are read when they are first used.
+///
+/// Each pattern is read together with its variation files, and kept until the project is
+/// dropped. A pattern that can't be read doesn't stop the others from being read.
+#[derive(Debug)]
+pub struct LazyProject {
+    pub path: PathBuf,
+    pub settings: Settings,
+    samples: OnceLock<Result<Samples>>,
+    patterns: Vec<LazyPattern>,
+}
+
+#[derive(Debug)]
+struct LazyPattern {
+    number: u8,
+    path: PathBuf,
+    pattern: OnceLock<Result<Pattern>>,
+}
+
+impl LazyProject {
+    /// Read the settings of the project in the directory `path`, and find its pattern files
+    pub fn open(path: &Path) -> Result<Self> {
+        if !path.is_dir() {
+            return Err(ParseError(format!(
+                "Provided project dir {:?} is not a directory",
+                &path
+            )));
+        }
+        let settings = Settings::read(&path.join("settings"))?;
+        let patterns_path = path.join("patterns");
+        let patterns = if patterns_path.exists() {
+            Pattern::pattern_files(patterns_path.as_path(), "")?
+        } else {
+            vec![]
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            settings,
+            samples: OnceLock::new(),
+            patterns: patterns
+                .into_iter()
+                .map(|(name, number)| LazyPattern {
+                    number,
+                    path: patterns_path.join(name),
+                    pattern: OnceLock::new(),
+                })
+                .collect(),
+        })
+    }
+
+    pub fn samples(&self) -> Result<&Samples> {
+        self.samples
+            .get_or_init(|| Samples::read(&self.path.join("samples").join("samplesMetadata")))
+            .as_ref()
+            .map_err(|e| e.clone())
+    }
+
+    /// The numbers of the project's pattern files, without reading them
+    pub fn pattern_numbers(&self) -> Vec<u8> {
+        self.patterns.iter().map(|p| p.number).collect()
+    }
+
+    /// Get a pattern by its number, reading it if this is the first time it's used. None if
+    /// the project has no such pattern.
+    pub fn pattern(&self, number: u8) -> Option<Result<&Pattern>> {
+        let lazy = self.patterns.iter().find(|p| p.number == number)?;
+        Some(
+            lazy.pattern
+                .get_or_init(|| Pattern::read(&lazy.path, lazy.number))
+                .as_ref()
+                .map_err(|e| e.clone()),
+        )
+    }
+
+    /// Read every pattern, with the number of each
+    pub fn patterns(&self) -> impl Iterator<Item = (u8, Result<&Pattern>)> {
+        self.patterns
+            .iter()
+            .filter_map(|p| Some((p.number, self.pattern(p.number)?)))
+    }
+
+    /// Read all of the project, failing if any part of it can't be read
+    pub fn load(&self) -> Result<Project> {
+        Ok(Project {
+            settings: self.settings.clone(),
+            samples: self.samples()?.clone(),
+            patterns: self
+                .patterns()
+                .map(|(_, p)| p.cloned())
+                .collect::<Result<Vec<Pattern>>>()?,
+        })
+    }
+}
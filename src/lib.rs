@@ -1,17 +1,60 @@
 use std::fmt;
-use std::fs::File;
+#[cfg(feature = "fs")]
+use std::fs::{self, File};
 use std::io::Read;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 use arr_macro::arr;
 use byteorder::{ByteOrder, LittleEndian};
-use glob::glob;
-use regex::Regex;
-
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "zip")]
+mod archive;
+mod build;
+#[cfg(feature = "fs")]
+mod card;
+mod diff;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "fs")]
+mod lazy;
+mod merge;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod play_mode;
+#[cfg(feature = "python")]
+mod python;
 mod reader;
+pub mod render;
+mod samples;
+mod step_ref;
+mod text;
+pub mod units;
+mod validate;
+mod vfs;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod writer;
+#[cfg(feature = "zip")]
+pub use archive::ZipFiles;
+#[cfg(feature = "fs")]
+pub use card::{ProjectEntry, SampleFolder, SampleLibrary, SdCard};
+pub use diff::{diff, Change};
+#[cfg(feature = "fs")]
+pub use lazy::LazyProject;
+pub use merge::{merge, Conflict};
+pub use play_mode::PlayMode;
 use reader::Reader;
+pub use samples::{PruneReport, SampleFile};
+use step_ref::read_step;
+pub use step_ref::StepRef;
+pub use validate::Violation;
+pub use vfs::Vfs;
+use writer::Writer;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 pub struct ParseError(String);
 
 impl fmt::Debug for ParseError {
@@ -30,7 +73,22 @@ impl std::error::Error for ParseError {}
 
 type Result<T> = std::result::Result<T, ParseError>;
 
+/// Read all of `source`, which holds a `what` file
+fn read_all(mut source: impl Read, what: &str) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    source
+        .read_to_end(&mut buf)
+        .map_err(|e| ParseError(format!("Cannot read {} file: {}", what, e)))?;
+    Ok(buf)
+}
+
+#[cfg(feature = "fs")]
+fn write_all(path: &Path, bytes: &[u8]) -> Result<()> {
+    fs::write(path, bytes).map_err(|e| ParseError(format!("Cannot write {:?}: {}", path, e)))
+}
+
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Project {
     pub settings: Settings,
     pub samples: Samples,
@@ -38,6 +96,7 @@ pub struct Project {
 }
 
 impl Project {
+    #[cfg(feature = "fs")]
     pub fn read(path: &Path) -> Result<Self> {
         if !path.is_dir() {
             return Err(ParseError(format!(
@@ -45,19 +104,29 @@ impl Project {
                 &path
             )));
         }
-        let settings = Settings::read(&path.join("settings"))?;
-        let samples = Samples::read(&path.join("samples").join("samplesMetadata"))?;
-        let patterns = Pattern::read_patterns(&path.join("patterns"))?;
+        Self::from_dir(path)
+    }
 
-        Ok(Self {
-            settings,
-            samples,
-            patterns,
-        })
+    /// Write the project to the directory `path`, creating it if needed.
+    ///
+    /// Only the project files are written: sample audio files are not part of a `Project`.
+    #[cfg(feature = "fs")]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        // Projects on the device have a patterns directory even when they have no patterns
+        let patterns = path.join("patterns");
+        fs::create_dir_all(&patterns)
+            .map_err(|e| ParseError(format!("Cannot create {:?}: {}", patterns, e)))?;
+        self.write_files(&mut path.to_path_buf())
+    }
+
+    /// Get a pattern by its number
+    pub fn pattern(&self, number: u8) -> Option<&Pattern> {
+        self.patterns.iter().find(|p| p.number == number)
     }
 }
 
 #[derive(PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Settings {
     pub name: String,
     pub directory: String,
@@ -65,47 +134,55 @@ pub struct Settings {
     pub jack_cc_mapping: Vec<CCMapping>,
     pub usb_cc_mapping: Vec<CCMapping>,
     // TODO
-    pub x20: Vec<u8>, // Unknown 5 bytes
-    pub xa8: Vec<u8>, // Unknown 2 bytes
-    pub xb0: Vec<u8>, // Unknown 2 bytes
-    pub x90: Vec<u8>, // Unknown 11? bytes
+    pub x20: Vec<u8>,  // Unknown 5 bytes
+    pub xa8: Vec<u8>,  // Unknown 2 bytes
+    pub xb0: Vec<u8>,  // Unknown 2 bytes
+    pub x90: Vec<u8>,  // Unknown 11? bytes
+    pub rest: Vec<u8>, // TODO
 }
 
 impl Settings {
+    #[cfg(feature = "fs")]
     pub fn read(path: &Path) -> Result<Self> {
-        let mut file =
-            File::open(path).map_err(|_| ParseError(format!("No settings file present")))?;
+        let file =
+            File::open(path).map_err(|_| ParseError("No settings file present".to_string()))?;
+        Self::from_reader(file)
+    }
 
-        let mut buf: Vec<u8> = vec![];
-        file.read_to_end(&mut buf).unwrap();
-        let reader = Reader::new(buf);
+    pub fn from_reader(source: impl Read) -> Result<Self> {
+        Self::from_bytes(&read_all(source, "settings")?)
+    }
+
+    /// Parse the contents of a settings file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
 
         let mut attrs = Self::default();
-        Self::attrs_from_reader(&reader, &mut attrs)?;
+        Self::attrs_from_reader(&mut reader, &mut attrs)?;
 
         Ok(attrs)
     }
 
-    fn attrs_from_reader(reader: &Reader, settings: &mut Self) -> Result<()> {
-        let mut tag = reader.read();
+    fn attrs_from_reader(reader: &mut Reader, settings: &mut Self) -> Result<()> {
+        let mut tag = reader.read()?;
         let mut abort = false;
         while tag != 0xc2 {
             // Elements in the CCMapping begin with 0xC2
             match tag {
                 // name
-                0x12 => settings.name = reader.read_string(reader.read() as usize),
+                0x12 => settings.name = reader.read_short_string()?,
                 // directory
-                0x62 => settings.directory = reader.read_string(reader.read() as usize),
+                0x62 => settings.directory = reader.read_short_string()?,
                 // bpm
                 0x85 => {
-                    reader.read(); // TODO Unknown byte (always 01?)
-                    settings.bpm = LittleEndian::read_f32(reader.read_bytes(4));
+                    reader.read()?; // TODO Unknown byte (always 01?)
+                    settings.bpm = LittleEndian::read_f32(reader.read_bytes(4)?);
                 }
                 // unknowns TODO
-                0x20 => settings.x20 = reader.read_bytes(5).to_vec(),
-                0x90 => settings.x90 = reader.read_bytes(11).to_vec(),
-                0xA8 => settings.xa8 = reader.read_bytes(2).to_vec(),
-                0xB0 => settings.xb0 = reader.read_bytes(2).to_vec(),
+                0x20 => settings.x20 = reader.read_bytes(5)?.to_vec(),
+                0x90 => settings.x90 = reader.read_bytes(11)?.to_vec(),
+                0xA8 => settings.xa8 = reader.read_bytes(2)?.to_vec(),
+                0xB0 => settings.xb0 = reader.read_bytes(2)?.to_vec(),
                 t => {
                     println!(
                         "Error: Unknown tag ({}). Aborting parsing project settings.",
@@ -115,19 +192,51 @@ impl Settings {
                     break;
                 }
             }
-            tag = reader.read();
+            tag = reader.read()?;
         }
         if !abort {
             reader.step_back(); // Replace the last 0xC2
             settings.jack_cc_mapping = (0..16)
-                .map(|_| CCMapping::from_reader(&reader))
+                .map(|_| CCMapping::from_reader(reader))
                 .collect::<Result<Vec<CCMapping>>>()?;
             settings.usb_cc_mapping = (0..16)
-                .map(|_| CCMapping::from_reader(&reader))
+                .map(|_| CCMapping::from_reader(reader))
                 .collect::<Result<Vec<CCMapping>>>()?;
+            settings.rest = reader.rest().to_vec();
         }
         Ok(())
     }
+
+    #[cfg(feature = "fs")]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        write_all(path, &self.to_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        // Tags are written in the order the device writes them
+        writer.write(0x12);
+        writer.write_string(&self.name);
+        if !self.x20.is_empty() {
+            writer.write(0x20);
+            writer.write_bytes(&self.x20);
+        }
+        writer.write(0x62);
+        writer.write_string(&self.directory);
+        writer.write_bytes(&[0x85, 0x01]);
+        writer.write_f32(self.bpm);
+        for (tag, bytes) in [(0x90, &self.x90), (0xA8, &self.xa8), (0xB0, &self.xb0)] {
+            if !bytes.is_empty() {
+                writer.write(tag);
+                writer.write_bytes(bytes);
+            }
+        }
+        for mapping in self.jack_cc_mapping.iter().chain(&self.usb_cc_mapping) {
+            mapping.to_writer(&mut writer);
+        }
+        writer.write_bytes(&self.rest);
+        writer.into_bytes()
+    }
 }
 
 impl fmt::Debug for Settings {
@@ -145,6 +254,7 @@ impl fmt::Debug for Settings {
 }
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CCMapping {
     pub u_first_bytes: [u8; 4], // TODO
     pub cutoff: u8,
@@ -158,44 +268,144 @@ pub struct CCMapping {
 }
 
 impl CCMapping {
-    fn from_reader(reader: &Reader) -> Result<Self> {
-        assert_eq!(reader.read(), 0xC2); // First byte probably tag (0xC2)
+    fn from_reader(reader: &mut Reader) -> Result<Self> {
+        reader.read_tag(0xC2, "CC mapping")?; // First byte probably tag (0xC2)
         Ok(Self {
-            u_first_bytes: reader.read_bytes(4).try_into().unwrap(),
-            cutoff: reader.read(),
-            resonance: reader.read(),
-            sample_attack: reader.read(),
-            sample_decay: reader.read(),
-            reverb_send: reader.read(),
-            delay_send: reader.read(),
-            overdrive: reader.read(),
-            bit_depth: reader.read(),
+            u_first_bytes: reader.read_bytes(4)?.try_into().unwrap(),
+            cutoff: reader.read()?,
+            resonance: reader.read()?,
+            sample_attack: reader.read()?,
+            sample_decay: reader.read()?,
+            reverb_send: reader.read()?,
+            delay_send: reader.read()?,
+            overdrive: reader.read()?,
+            bit_depth: reader.read()?,
         })
     }
+
+    fn to_writer(&self, writer: &mut Writer) {
+        writer.write(0xC2);
+        writer.write_bytes(&self.u_first_bytes);
+        writer.write_bytes(&[
+            self.cutoff,
+            self.resonance,
+            self.sample_attack,
+            self.sample_decay,
+            self.reverb_send,
+            self.delay_send,
+            self.overdrive,
+            self.bit_depth,
+        ]);
+    }
 }
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Samples {
+    /// The folder of each of the 256 sample slots, as an index into `folders`
+    pub slot_folders: Vec<u8>,
+    pub x10: u8, // TODO Unknown; 0 when absent
+    /// The names of the sample folders
+    pub folders: Vec<String>,
     pub rest: Vec<u8>, // TODO
 }
 impl Samples {
+    #[cfg(feature = "fs")]
     pub fn read(path: &Path) -> Result<Self> {
-        let mut file = File::open(path)
+        let file = File::open(path)
             .map_err(|_| ParseError(format!("Cannot read sample file: {:?}", &path)))?;
+        Self::from_reader(file)
+    }
+
+    pub fn from_reader(source: impl Read) -> Result<Self> {
+        Self::from_bytes(&read_all(source, "samplesMetadata")?)
+    }
+
+    /// Parse the contents of a samplesMetadata file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+
+        let mut samples = Self {
+            slot_folders: vec![],
+            x10: 0,
+            folders: vec![],
+            rest: vec![],
+        };
+        while reader.pos() < reader.buffer_len() {
+            match reader.read()? {
+                // A slot
+                0x0A => {
+                    let end = reader.read_variable_quantity()? + reader.pos();
+                    let mut folder = 0;
+                    while reader.pos() < end {
+                        match reader.read()? {
+                            0x08 => folder = reader.read_variable_quantity()? as u8,
+                            t => {
+                                return Err(ParseError(format!(
+                                    "Unknown sample slot tag {:02X}",
+                                    t
+                                )))
+                            }
+                        }
+                    }
+                    samples.slot_folders.push(folder);
+                }
+                0x10 => samples.x10 = reader.read_variable_quantity()? as u8,
+                // A folder name
+                0x1A => {
+                    let len = reader.read_variable_quantity()?;
+                    samples.folders.push(reader.read_string(len)?);
+                }
+                _ => {
+                    reader.step_back();
+                    break;
+                }
+            }
+        }
+        samples.rest = reader.rest().to_vec();
+
+        Ok(samples)
+    }
 
-        let mut buf: Vec<u8> = vec![];
-        file.read_to_end(&mut buf).unwrap();
-        let reader = Reader::new(buf);
+    #[cfg(feature = "fs")]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        write_all(path, &self.to_bytes())
+    }
 
-        let rest = reader.rest();
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        for &folder in &self.slot_folders {
+            // Folder 0 is left out
+            let mut slot = Writer::new();
+            if folder != 0 {
+                slot.write(0x08);
+                slot.write_variable_quantity(folder as usize);
+            }
+            writer.write_message(0x0A, &slot.into_bytes());
+        }
+        if self.x10 != 0 {
+            writer.write(0x10);
+            writer.write_variable_quantity(self.x10 as usize);
+        }
+        for folder in &self.folders {
+            writer.write(0x1A);
+            writer.write_string(folder);
+        }
+        writer.write_bytes(&self.rest);
+        writer.into_bytes()
+    }
 
-        Ok(Self { rest })
+    /// The name of the folder of a sample slot
+    pub fn folder_name(&self, slot: usize) -> Option<&str> {
+        let folder = *self.slot_folders.get(slot)?;
+        self.folders.get(folder as usize).map(|f| f.as_str())
     }
 }
 
 impl fmt::Debug for Samples {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Samples")
+            .field("folders", &self.folders)
             // .field(
             //     "rest",
             //     &format!(
@@ -212,14 +422,20 @@ type AudioTrackVariations = [Option<Track<Step>>; 16];
 type MidiTrackVariations = [Option<Track<MidiStep>>; 16];
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pattern {
     pub number: u8,
     pub audio_tracks: [AudioTrackVariations; 8],
     pub midi_tracks: [MidiTrackVariations; 8],
     pub rest: Vec<u8>, // TODO
+    /// The track numbers (MIDI tracks from 8), variations and contents of the track files of
+    /// active variations. The device leaves them as they were when the variations were last
+    /// inactive, so they are kept to be written back.
+    active_track_files: Vec<(usize, usize, Vec<u8>)>,
 }
 impl Pattern {
     /// Read a pattern directory
+    #[cfg(feature = "fs")]
     pub fn read_patterns(path: &Path) -> Result<Vec<Self>> {
         if !path.is_dir() {
             return Err(ParseError(format!(
@@ -227,115 +443,147 @@ impl Pattern {
                 &path
             )));
         }
-
-        let mut patterns = vec![];
-        for entry in glob(&format!("{}/*.pattern", path.to_str().unwrap()))
-            .map_err(|_| ParseError(format!("Could not read pattern dir")))?
-        {
-            match entry {
-                Ok(path) => {
-                    let re = Regex::new(r"(\d+).pattern$").unwrap();
-                    let pattern_number = if let Some(n) = re.captures(path.to_str().unwrap()) {
-                        n.get(1).unwrap().as_str().parse().map_err(|_| {
-                            ParseError(format!("Invalid pattern file name: {:?}", &path))
-                        })?
-                    } else {
-                        return Err(ParseError(format!(
-                            "Invalid pattern file name: {:?}",
-                            &path
-                        )));
-                    };
-                    patterns.push(Self::read(&path, pattern_number)?);
-                }
-                _ => return Err(ParseError(format!("Could not read pattern dir"))),
-            }
-        }
-
-        Ok(patterns)
+        Self::read_dir(path)
     }
 
     /// Read a particular pattern file. Will also read any track files that match the pattern number
+    #[cfg(feature = "fs")]
     pub fn read(path: &Path, number: u8) -> Result<Self> {
-        let mut file = File::open(path)
-            .map_err(|_| ParseError(format!("Cannot read pattern file: {:?}", &path)))?;
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str()))
+        else {
+            return Err(ParseError(format!("Cannot read pattern file: {:?}", &path)));
+        };
+        Self::read_in(dir, "", name, number)
+    }
 
-        let mut buf: Vec<u8> = vec![];
-        file.read_to_end(&mut buf).unwrap();
-        let reader = Reader::new(buf);
+    pub fn from_reader(source: impl Read, number: u8) -> Result<Self> {
+        Self::from_bytes(&read_all(source, "pattern")?, number)
+    }
+
+    /// Parse the contents of a pattern file, which holds the active variation of each track.
+    /// Variations from track files can be added with
+    /// [add_variations](Self::add_variations).
+    pub fn from_bytes(bytes: &[u8], number: u8) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
 
         let mut audio_tracks = arr![arr![None; 16]; 8];
         let mut midi_tracks = arr![arr![None; 16]; 8];
-        // dbg!(path);
-        for track in 0..8 {
-            let t = Track::from_reader(&reader, track, 0, false)?;
+        for (track, variations) in audio_tracks.iter_mut().enumerate() {
+            let t = Track::parse(&mut reader, track, 0, false)?;
             let v = t.variation;
-            audio_tracks[track][v] = Some(t);
+            variations[v] = Some(t);
         }
-        for track in 0..8 {
-            let t = Track::from_reader(&reader, track, 0, false)?;
+        for (track, variations) in midi_tracks.iter_mut().enumerate() {
+            let t = Track::parse(&mut reader, track, 0, false)?;
             let v = t.variation;
-            midi_tracks[track][v] = Some(t);
+            variations[v] = Some(t);
         }
 
-        let rest = reader.rest();
-
-        let (audio_variation, midi_variation) =
-            Self::read_variations(path.parent().unwrap(), number)?;
-        for variation in audio_variation {
-            let track = variation.number;
-            let v = variation.variation;
-            if audio_tracks[track][v].is_some() {
-                continue;
-            }
-            audio_tracks[track][v] = Some(variation);
-        }
-        for variation in midi_variation {
-            let track = variation.number;
-            let v = variation.variation;
-            if midi_tracks[track][v].is_some() {
-                continue;
-            }
-            midi_tracks[track][v] = Some(variation);
-        }
+        let rest = reader.rest().to_vec();
 
         Ok(Self {
             number,
             audio_tracks,
             midi_tracks,
             rest,
+            active_track_files: vec![],
         })
     }
 
-    fn read_variations(
-        path: &Path,
-        pattern_number: u8,
-    ) -> Result<(Vec<Track<Step>>, Vec<Track<MidiStep>>)> {
-        let mut audio: Vec<Track<Step>> = vec![];
-        let mut midi: Vec<Track<MidiStep>> = vec![];
-        for track in 0..16 {
-            let mut track_files = glob(&format!(
-                "{}/{}-{}-*.track",
-                path.to_str().unwrap(),
-                pattern_number,
-                track
-            ))
-            .map_err(|_| ParseError(format!("Could not read track dir")))?;
-
-            if track_files.next().is_some() {
-                for variation in 0..16 {
-                    let track_path =
-                        path.join(&format!("{}-{}-{}.track", pattern_number, track, variation));
-                    if track_path.is_file() {
-                        if track < 8 {
-                            audio.push(Track::read(&track_path, track, variation)?);
-                        } else {
-                            midi.push(Track::read(&track_path, track - 8, variation)?);
-                        }
-                    }
-                }
+    /// Add variations read from track files. Variations that are already in the pattern file
+    /// are kept, and their track files are written back as they were.
+    pub fn add_variations(&mut self, (audio, midi): (Vec<Track<Step>>, Vec<Track<MidiStep>>)) {
+        for variation in audio {
+            let slot = &mut self.audio_tracks[variation.number][variation.variation];
+            match slot {
+                Some(_) => self.active_track_files.push((
+                    variation.number,
+                    variation.variation,
+                    variation.to_bytes(),
+                )),
+                None => *slot = Some(variation),
+            }
+        }
+        for variation in midi {
+            let slot = &mut self.midi_tracks[variation.number][variation.variation];
+            match slot {
+                Some(_) => self.active_track_files.push((
+                    variation.number + 8,
+                    variation.variation,
+                    variation.to_bytes(),
+                )),
+                None => *slot = Some(variation),
+            }
+        }
+    }
+
+    /// Write the pattern file, a track file for every variation that isn't active, and the
+    /// track files that active variations were read with, into the patterns directory `dir`
+    #[cfg(feature = "fs")]
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let mut dir = dir.to_path_buf();
+        for (name, bytes) in self.files()? {
+            vfs::write_file(&mut dir, &name, &bytes)?;
+        }
+        Ok(())
+    }
+
+    /// The names and contents of the pattern file and the variation files that `write` writes
+    pub(crate) fn files(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut files = vec![(format!("{}.pattern", self.number), self.to_bytes()?)];
+        let audio = self
+            .audio_tracks
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|t| (t.number, t.variation, t.is_default, t.to_bytes()));
+        let midi = self
+            .midi_tracks
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|t| (t.number + 8, t.variation, t.is_default, t.to_bytes()));
+        for (track, variation, is_default, bytes) in audio.chain(midi) {
+            let bytes = if is_default {
+                self.active_track_files
+                    .iter()
+                    .find(|(t, v, _)| (*t, *v) == (track, variation))
+                    .map(|(_, _, bytes)| bytes.clone())
+            } else {
+                Some(bytes)
+            };
+            if let Some(bytes) = bytes {
+                let name = format!("{}-{}-{}.track", self.number, track, variation);
+                files.push((name, bytes));
             }
         }
-        Ok((audio, midi))
+        Ok(files)
+    }
+
+    /// The contents of the pattern file: the active variation of every track. Fails if a
+    /// track has no active variation.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let missing = |kind: &str, track: usize| {
+            ParseError(format!(
+                "{} track {} of pattern {} has no active variation",
+                kind, track, self.number
+            ))
+        };
+        let mut writer = Writer::new();
+        for track in 0..8 {
+            let t = self
+                .active_audio_track(track)
+                .ok_or_else(|| missing("Audio", track))?;
+            writer.write_message(0x0A, &t.to_bytes());
+        }
+        for track in 0..8 {
+            let t = self
+                .active_midi_track(track)
+                .ok_or_else(|| missing("MIDI", track))?;
+            writer.write_message(0x0A, &t.to_bytes());
+        }
+        writer.write_bytes(&self.rest);
+        Ok(writer.into_bytes())
     }
 
     /// Get the first variation of a track
@@ -347,6 +595,16 @@ impl Pattern {
     pub fn midi_track(&self, n: usize) -> &Track<MidiStep> {
         self.midi_tracks[n][0].as_ref().unwrap()
     }
+
+    /// Get the variation of a track that was active when the pattern was saved
+    pub fn active_audio_track(&self, n: usize) -> Option<&Track<Step>> {
+        self.audio_tracks[n].iter().flatten().find(|t| t.is_default)
+    }
+
+    /// Get the variation of a track that was active when the pattern was saved
+    pub fn active_midi_track(&self, n: usize) -> Option<&Track<MidiStep>> {
+        self.midi_tracks[n].iter().flatten().find(|t| t.is_default)
+    }
 }
 
 impl fmt::Debug for Pattern {
@@ -368,12 +626,14 @@ impl fmt::Debug for Pattern {
 }
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Track<S: TrackStep + Clone> {
     pub number: usize,
     pub variation: usize,
     pub steps: Vec<S>,
     // Percentage 25-75
     pub swing: u8,
+    /// See [PlayMode]
     pub play_mode: u8,
     pub track_speed: TrackSpeed,
     pub is_default: bool,
@@ -381,8 +641,8 @@ pub struct Track<S: TrackStep + Clone> {
 }
 
 impl<S: TrackStep + Clone> Track<S> {
-    fn from_reader(
-        reader: &Reader,
+    fn parse(
+        reader: &mut Reader,
         number: usize,
         variation: usize,
         from_file: bool,
@@ -391,8 +651,8 @@ impl<S: TrackStep + Clone> Track<S> {
             if from_file {
                 reader.buffer_len()
             } else {
-                assert_eq!(reader.read(), 0x0A); // First tag (0x0A)
-                reader.read_variable_quantity()
+                reader.read_tag(0x0A, "track")?; // First tag (0x0A)
+                reader.read_variable_quantity()?
             }
         };
         // println!("Reading track {:?} {} with len {}", ty, number, track_len);
@@ -403,18 +663,35 @@ impl<S: TrackStep + Clone> Track<S> {
             .collect::<Result<Vec<S>>>()?;
 
         let attrs = TrackAttrs::from_reader(reader, track_len + start_pos)?;
-        assert!(attrs.num_steps > 0 && attrs.num_steps < 65);
+        if attrs.num_steps == 0 || attrs.num_steps > 64 {
+            return Err(ParseError(format!(
+                "Track {} has {} steps, not 1-64",
+                number, attrs.num_steps
+            )));
+        }
 
         let bytes_advanced = reader.pos() - start_pos;
-        assert_eq!(bytes_advanced, track_len);
+        if bytes_advanced != track_len {
+            return Err(ParseError(format!(
+                "Track {} is {} bytes long, but {} bytes were read",
+                number, track_len, bytes_advanced
+            )));
+        }
+        let variation = if from_file {
+            variation
+        } else {
+            attrs.variation as usize
+        };
+        if variation >= 16 {
+            return Err(ParseError(format!(
+                "Track {} is variation {}, not 0-15",
+                number, variation
+            )));
+        }
         Ok(Self {
             number,
-            variation: if from_file {
-                variation
-            } else {
-                attrs.variation as usize
-            },
-            steps: steps[0..(attrs.num_steps as usize)].try_into().unwrap(),
+            variation,
+            steps: steps[0..(attrs.num_steps as usize)].into(),
             swing: attrs.swing,
             play_mode: attrs.play_mode,
             track_speed: attrs.track_speed,
@@ -423,15 +700,75 @@ impl<S: TrackStep + Clone> Track<S> {
         })
     }
 
+    #[cfg(feature = "fs")]
     pub fn read(path: &Path, track_number: usize, variation_number: usize) -> Result<Self> {
-        let mut file = File::open(path)
+        let file = File::open(path)
             .map_err(|_| ParseError(format!("Cannot read track file: {:?}", &path)))?;
+        Self::from_reader(file, track_number, variation_number)
+    }
+
+    pub fn from_reader(
+        source: impl Read,
+        track_number: usize,
+        variation_number: usize,
+    ) -> Result<Self> {
+        Self::from_bytes(&read_all(source, "track")?, track_number, variation_number)
+    }
+
+    /// Parse the contents of a track file, which holds a variation that isn't active. MIDI
+    /// tracks are numbered from 0 here, rather than from 8 as in the names of track files.
+    pub fn from_bytes(bytes: &[u8], track_number: usize, variation_number: usize) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        Self::parse(&mut reader, track_number, variation_number, true)
+    }
 
-        let mut buf: Vec<u8> = vec![];
-        file.read_to_end(&mut buf).unwrap();
-        let reader = Reader::new(buf);
+    #[cfg(feature = "fs")]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        write_all(path, &self.to_bytes())
+    }
+
+    /// The steps and attributes of the track, as stored in a track file or (with a tag and
+    /// length) in a pattern file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        // The device always stores 64 steps; those past the end of the track are empty
+        for step in &self.steps {
+            step.to_writer(&mut writer);
+        }
+        for _ in self.steps.len()..64 {
+            S::write_empty(&mut writer, !self.is_default);
+        }
 
-        Self::from_reader(&reader, track_number, variation_number, true)
+        let attrs = &self.attrs;
+        writer.write_bytes(&[0x10, self.steps.len() as u8]);
+        if attrs.ux18 != 0 {
+            writer.write_bytes(&[0x18, attrs.ux18]);
+        }
+        match self.track_speed {
+            TrackSpeed::Fraction(n, d) => writer.write_bytes(&[0x20, n, 0x28, d]),
+            // A zero numerator is left out
+            TrackSpeed::Paused => writer.write_bytes(&[0x28, 0x01]),
+        }
+        // Track files store the variation that was active when they were saved
+        let variation = if self.is_default {
+            self.variation as u8
+        } else {
+            attrs.variation
+        };
+        for (tag, value) in [
+            (0x30, variation),
+            (0x38, self.swing),
+            (0x40, self.play_mode),
+        ] {
+            if value != 0 {
+                writer.write_bytes(&[tag, value]);
+            }
+        }
+        if !attrs.variations.is_empty() {
+            let variations: Vec<u8> = attrs.variations.iter().map(|&v| v as u8).collect();
+            writer.write_message(0x4A, &variations);
+        }
+        writer.into_bytes()
     }
 }
 
@@ -450,6 +787,7 @@ impl<S: TrackStep + Clone + fmt::Debug> fmt::Debug for Track<S> {
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TrackAttrs {
     num_steps: u8,
     // Percentage 25-75
@@ -465,50 +803,50 @@ pub struct TrackAttrs {
 }
 
 impl TrackAttrs {
-    fn from_reader(reader: &Reader, max_pos: usize) -> Result<Self> {
+    fn from_reader(reader: &mut Reader, max_pos: usize) -> Result<Self> {
         let mut attrs = TrackAttrs::default();
 
         while reader.pos() < max_pos {
-            let val = reader.read();
+            let val = reader.read()?;
             match val {
-                0x10 => attrs.num_steps = reader.read(),
-                0x38 => attrs.swing = reader.read(),
-                0x40 => attrs.play_mode = reader.read(),
+                0x10 => attrs.num_steps = reader.read()?,
+                0x38 => attrs.swing = reader.read()?,
+                0x40 => attrs.play_mode = reader.read()?,
                 0x20 => {
                     if let TrackSpeed::Fraction(_, d) = attrs.track_speed {
-                        attrs.track_speed = match reader.read() {
+                        attrs.track_speed = match reader.read()? {
                             0 => TrackSpeed::Paused,
                             n => TrackSpeed::Fraction(n, d),
                         };
                     } else {
-                        attrs.track_speed = TrackSpeed::Fraction(reader.read(), 1)
+                        attrs.track_speed = TrackSpeed::Fraction(reader.read()?, 1)
                     }
                 }
                 0x28 => {
                     if let TrackSpeed::Fraction(n, _) = attrs.track_speed {
-                        attrs.track_speed = match reader.read() {
+                        attrs.track_speed = match reader.read()? {
                             0 => TrackSpeed::Paused,
                             d => TrackSpeed::Fraction(n, d),
                         };
                     } else {
-                        reader.read(); // discard
+                        reader.read()?; // discard
                     }
                 }
-                0x30 => attrs.variation = reader.read(),
+                0x30 => attrs.variation = reader.read()?,
                 0x4a => {
-                    let len = reader.read();
+                    let len = reader.read()?;
                     attrs.variations = reader
-                        .read_bytes(len as usize)
+                        .read_bytes(len as usize)?
                         .iter()
                         .map(|&x| x != 0)
                         .collect();
                 }
-                0x18 => attrs.ux18 = reader.read(),
+                0x18 => attrs.ux18 = reader.read()?,
                 x => {
                     println!(
                         "Warning: encountered unknown track tag {:02X} with value {}",
                         x,
-                        reader.read()
+                        reader.read()?
                     );
                 }
             }
@@ -518,26 +856,43 @@ impl TrackAttrs {
     }
 }
 
-#[derive(PartialEq, Clone, Debug, Copy)]
+#[derive(PartialEq, Clone, Debug, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TrackSpeed {
     /// Numerator, Denominator
     Fraction(u8, u8),
+    #[default]
     Paused,
 }
 
-impl Default for TrackSpeed {
-    fn default() -> Self {
-        Self::Paused
-    }
-}
-
 pub trait TrackStep {
-    fn from_reader(reader: &Reader, number: usize) -> Result<Self>
+    fn from_reader(reader: &mut Reader, number: usize) -> Result<Self>
     where
         Self: Sized;
+
+    fn to_writer(&self, writer: &mut Writer);
+
+    /// Whether there is no note on this step
+    fn is_empty(&self) -> bool;
+
+    /// The number of the step, from 0
+    fn number(&self) -> usize;
+
+    fn set_number(&mut self, number: usize);
+
+    /// Write a step without a note. All of its values are 0 by default.
+    fn write_empty(writer: &mut Writer, _in_track_file: bool)
+    where
+        Self: Sized,
+    {
+        let mut empty = vec![0x0A, 0x2C];
+        empty.extend([0; 44]);
+        writer.write_message(0x0A, &empty);
+    }
 }
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Step {
     /// Step number, 0 indexed
     pub number: usize,
@@ -557,7 +912,7 @@ pub struct Step {
     pub overdrive: u16,
     /// 4-16
     pub bit_depth: u8,
-    /// -10000 is -11/24; 10000 is +11/24
+    /// In 24ths of a step: -11 to +11
     pub micro_move: i16,
     /// 10000 is 100%; 100 = 1%
     pub reverb: i16,
@@ -584,67 +939,74 @@ pub struct Step {
 }
 
 impl TrackStep for Step {
-    fn from_reader(reader: &Reader, number: usize) -> Result<Self> {
-        assert_eq!(reader.read(), 0x0A, "Error reading {}nth step", number); // first byte tag (0x0A)
-        let len = reader.read_variable_quantity(); // Length of step data
+    fn from_reader(reader: &mut Reader, number: usize) -> Result<Self> {
+        Ok(StepRef::from_reader(reader, number)?.to_step())
+    }
 
-        let start_pos = reader.pos();
-        // println!("{}nth step, length {} ({:02x})", number, len, len);
-        assert_eq!(reader.read(), 0x0A); // Second tag (0x0A)
-        let num_elements = reader.read_variable_quantity(); // Length of step data
-        assert_eq!(num_elements, 44); // I've never seen a value that's not 44
-
-        let volume = LittleEndian::read_u16(reader.read_bytes(2));
-        let pan = LittleEndian::read_i16(reader.read_bytes(2));
-        let filter_cutoff = LittleEndian::read_i16(reader.read_bytes(2));
-        let filter_resonance = LittleEndian::read_u16(reader.read_bytes(2));
-        let bit_depth = LittleEndian::read_u16(reader.read_bytes(2)) as u8;
-        let overdrive = LittleEndian::read_u16(reader.read_bytes(2));
-        let note = LittleEndian::read_u16(reader.read_bytes(2)) as u8;
-        let delay = LittleEndian::read_i16(reader.read_bytes(2));
-        let reverb = LittleEndian::read_i16(reader.read_bytes(2));
-        let sample = LittleEndian::read_u16(reader.read_bytes(2));
-        let sample_start = LittleEndian::read_i16(reader.read_bytes(2));
-        let sample_end = LittleEndian::read_i16(reader.read_bytes(2));
-        let micro_tune = LittleEndian::read_i16(reader.read_bytes(2));
-        let sample_attack = LittleEndian::read_u16(reader.read_bytes(2));
-        let sample_decay = LittleEndian::read_u16(reader.read_bytes(2));
-        let sample_folder = LittleEndian::read_u16(reader.read_bytes(2));
-        let repeat_type = LittleEndian::read_u16(reader.read_bytes(2));
-        let repeat_grid = LittleEndian::read_u16(reader.read_bytes(2));
-        let chance_type = LittleEndian::read_u16(reader.read_bytes(2));
-        let chance_action = LittleEndian::read_u16(reader.read_bytes(2));
-        let micro_move = LittleEndian::read_i16(reader.read_bytes(2));
+    fn is_empty(&self) -> bool {
+        Step::is_empty(self)
+    }
 
-        let bytes_advanced = reader.pos() - start_pos;
-        // Rest appears to be two empty bytes for empty notes and 8 bytes otherwise
-        let rest = reader.read_bytes(len - bytes_advanced); // Unknown data
+    fn number(&self) -> usize {
+        self.number
+    }
 
-        Ok(Self {
-            number,
-            sample,
-            note,
-            volume,
-            pan,
-            filter_cutoff,
-            filter_resonance,
-            micro_move,
-            micro_tune,
-            sample_start,
-            sample_end,
-            sample_attack,
-            sample_decay,
-            sample_folder,
-            repeat_type,
-            repeat_grid,
-            chance_type,
-            chance_action,
-            reverb,
-            delay,
-            overdrive,
-            bit_depth,
-            rest: rest.to_vec(),
-        })
+    fn set_number(&mut self, number: usize) {
+        self.number = number;
+    }
+
+    fn to_writer(&self, writer: &mut Writer) {
+        let mut data = Writer::new();
+        data.write_bytes(&[0x0A, 0x2C]);
+        data.write_u16(self.volume);
+        data.write_i16(self.pan);
+        data.write_i16(self.filter_cutoff);
+        data.write_u16(self.filter_resonance);
+        data.write_u16(self.bit_depth as u16);
+        data.write_u16(self.overdrive);
+        data.write_u16(self.note as u16);
+        data.write_i16(self.delay);
+        data.write_i16(self.reverb);
+        data.write_u16(self.sample);
+        data.write_i16(self.sample_start);
+        data.write_i16(self.sample_end);
+        data.write_i16(self.micro_tune);
+        data.write_u16(self.sample_attack);
+        data.write_u16(self.sample_decay);
+        data.write_u16(self.sample_folder);
+        data.write_u16(self.repeat_type);
+        data.write_u16(self.repeat_grid);
+        data.write_u16(self.chance_type);
+        data.write_u16(self.chance_action);
+        data.write_i16(self.micro_move);
+        write_step_rest(&mut data, &self.rest);
+        writer.write_message(0x0A, &data.into_bytes());
+    }
+
+    fn write_empty(writer: &mut Writer, in_track_file: bool) {
+        let mut empty = vec![0x0A, 0x2C];
+        empty.extend([0; 44]);
+        if in_track_file {
+            // Empty audio steps in track files have a bit depth of 4
+            empty[10] = 4;
+        }
+        writer.write_message(0x0A, &empty);
+    }
+}
+
+/// The rest of a step always includes the last two of its 44 bytes of values
+fn write_step_rest(writer: &mut Writer, rest: &[u8]) {
+    writer.write_bytes(rest);
+    for _ in rest.len()..2 {
+        writer.write(0);
+    }
+}
+
+impl Step {
+    /// Whether there is a note on this step
+    pub fn is_empty(&self) -> bool {
+        // Steps with a note have a trailing bitmask of the values that are set
+        self.rest.len() <= 2
     }
 }
 
@@ -703,6 +1065,7 @@ impl fmt::Debug for Step {
 }
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MidiStep {
     /// Step number, 0 indexed
     pub number: usize,
@@ -716,7 +1079,7 @@ pub struct MidiStep {
     pub note_length: u16,
     // Chord enum value
     pub chord: i16,
-    /// -10000 is -11/24; 10000 is +11/24
+    /// In 24ths of a step: -11 to +11
     pub micro_move: i16,
     /// -10000 is -100 cents; 10000 is +100 cents; 100 = 1 cent
     pub pitch_bend: Option<i16>,
@@ -740,46 +1103,50 @@ pub struct MidiStep {
     /// 0 = Play Step
     pub chance_action: u16,
 
+    /// The stored values of the parameters that aren't set, and of the unused sample end, as
+    /// 21 little-endian 16-bit numbers where those of set parameters are 0. The device leaves
+    /// whatever was there before, so they are kept to write the step back as it was. Empty
+    /// when they are all 0.
+    pub values: Vec<u8>,
     pub rest: Vec<u8>, // TODO
 }
 
 impl TrackStep for MidiStep {
-    fn from_reader(reader: &Reader, number: usize) -> Result<Self> {
-        assert_eq!(reader.read(), 0x0A, "Error reading {}nth step", number); // first byte tag (0x0A)
-        let len = reader.read_variable_quantity(); // Length of step data
-
-        let start_pos = reader.pos();
-        // println!("{}nth step, length {} ({:02x})", number, len, len);
-        assert_eq!(reader.read(), 0x0A); // Second tag (0x0A)
-        let num_elements = reader.read_variable_quantity(); // Length of step data
-        assert_eq!(num_elements, 44); // I've never seen a value that's not 44
-
-        let velocity = LittleEndian::read_u16(reader.read_bytes(2)) as u8;
-        let note_length = LittleEndian::read_u16(reader.read_bytes(2));
-        let mut cc74 = Some(LittleEndian::read_i16(reader.read_bytes(2)) as u8);
-        let mut cc71 = Some(LittleEndian::read_u16(reader.read_bytes(2)) as u8);
-        let mut cc13 = Some(LittleEndian::read_u16(reader.read_bytes(2)) as u8);
-        let mut cc12 = Some(LittleEndian::read_u16(reader.read_bytes(2)) as u8);
-        let note = LittleEndian::read_u16(reader.read_bytes(2)) as u8;
-        let mut cc19 = Some(LittleEndian::read_i16(reader.read_bytes(2)) as u8);
-        let mut cc17 = Some(LittleEndian::read_i16(reader.read_bytes(2)) as u8);
-        let channel = MidiChannel::from(LittleEndian::read_u16(reader.read_bytes(2)));
-        let chord = LittleEndian::read_i16(reader.read_bytes(2));
-        let _sample_end = LittleEndian::read_i16(reader.read_bytes(2)); // unused
-        let mut pitch_bend = Some(LittleEndian::read_i16(reader.read_bytes(2)));
-        let mut cc22 = Some(LittleEndian::read_u16(reader.read_bytes(2)) as u8);
-        let mut cc75 = Some(LittleEndian::read_u16(reader.read_bytes(2)) as u8);
-        let mut program = Some(LittleEndian::read_u16(reader.read_bytes(2)) as u8);
-        let mut repeat_type = LittleEndian::read_u16(reader.read_bytes(2));
-        let mut repeat_grid = LittleEndian::read_u16(reader.read_bytes(2));
-        let chance_type = LittleEndian::read_u16(reader.read_bytes(2));
-        let chance_action = LittleEndian::read_u16(reader.read_bytes(2));
-        let micro_move = LittleEndian::read_i16(reader.read_bytes(2));
-
-        let bytes_advanced = reader.pos() - start_pos;
-        // First five bytes are unknown. Last 3 are a bitmask when the note exists
-        let rest = reader.read_bytes(len - bytes_advanced);
-
+    fn from_reader(reader: &mut Reader, number: usize) -> Result<Self> {
+        let (values, rest) = read_step(reader, number)?;
+        // The values are 21 little-endian 16-bit numbers
+        let u16_at = |i: usize| LittleEndian::read_u16(&values[i * 2..]);
+        let i16_at = |i: usize| LittleEndian::read_i16(&values[i * 2..]);
+        let velocity = u16_at(0) as u8;
+        let note_length = u16_at(1);
+        let mut cc74 = Some(i16_at(2) as u8);
+        let mut cc71 = Some(u16_at(3) as u8);
+        let mut cc13 = Some(u16_at(4) as u8);
+        let mut cc12 = Some(u16_at(5) as u8);
+        let note = u16_at(6) as u8;
+        let mut cc19 = Some(i16_at(7) as u8);
+        let mut cc17 = Some(i16_at(8) as u8);
+        let channel = MidiChannel::from(u16_at(9));
+        let chord = i16_at(10);
+        let _sample_end = i16_at(11); // unused
+        let mut pitch_bend = Some(i16_at(12));
+        let mut cc22 = Some(u16_at(13) as u8);
+        let mut cc75 = Some(u16_at(14) as u8);
+        let mut program = Some(u16_at(15) as u8);
+        let mut repeat_type = u16_at(16);
+        let mut repeat_grid = u16_at(17);
+        let chance_type = u16_at(18);
+        let chance_action = u16_at(19);
+        let micro_move = i16_at(20);
+
+        // First five bytes of the rest are unknown. Last 3 are a bitmask when the note exists
+        if (3..8).contains(&rest.len()) {
+            return Err(ParseError(format!(
+                "MIDI step {} has {} bytes after its values, not 2 or fewer or 8 or more",
+                number,
+                rest.len()
+            )));
+        }
         if rest.len() > 2 {
             let m1 = rest[5];
             let m2 = rest[6];
@@ -841,7 +1208,7 @@ impl TrackStep for MidiStep {
             pitch_bend = None;
         }
 
-        Ok(Self {
+        let mut step = Self {
             number,
             channel,
             program,
@@ -863,8 +1230,112 @@ impl TrackStep for MidiStep {
             repeat_grid,
             chance_type,
             chance_action,
+            values: vec![],
             rest: rest.to_vec(),
-        })
+        };
+        let mut unset = values.to_vec();
+        for (i, value) in step.stored_values().iter().enumerate() {
+            if value.is_some() {
+                unset[i * 2..i * 2 + 2].fill(0);
+            }
+        }
+        if unset.iter().any(|&b| b != 0) {
+            step.values = unset;
+        }
+        Ok(step)
+    }
+
+    fn number(&self) -> usize {
+        self.number
+    }
+
+    fn set_number(&mut self, number: usize) {
+        self.number = number;
+    }
+
+    /// Values that are None are written as they were read, or as 0, and the bitmask of set
+    /// values is updated to match them
+    fn to_writer(&self, writer: &mut Writer) {
+        let mut data = Writer::new();
+        data.write_bytes(&[0x0A, 0x2C]);
+        for (i, value) in self.stored_values().into_iter().enumerate() {
+            data.write_u16(value.unwrap_or_else(|| {
+                self.values
+                    .get(i * 2..i * 2 + 2)
+                    .map_or(0, LittleEndian::read_u16)
+            }));
+        }
+
+        let mut rest = self.rest.clone();
+        if rest.len() > 7 {
+            let mut set = |byte: usize, bit: u8, is_set: bool| {
+                if is_set {
+                    rest[byte] |= 1 << bit;
+                } else {
+                    rest[byte] &= !(1 << bit);
+                }
+            };
+            set(5, 5, self.cc12.is_some());
+            set(5, 4, self.cc13.is_some());
+            set(5, 3, self.cc71.is_some());
+            set(5, 2, self.cc74.is_some());
+            set(6, 6, self.cc22.is_some());
+            set(6, 5, self.pitch_bend.is_some());
+            set(6, 1, self.cc17.is_some());
+            set(6, 0, self.cc19.is_some());
+            set(7, 1, self.program.is_some());
+            set(7, 0, self.cc75.is_some());
+            // Repeats read as 0 when unset, so a 0 can be stored either way
+            if self.repeat_grid != 0 {
+                set(7, 3, true);
+            }
+            if self.repeat_type != 0 {
+                set(7, 2, true);
+            }
+        }
+        write_step_rest(&mut data, &rest);
+        writer.write_message(0x0A, &data.into_bytes());
+    }
+
+    fn is_empty(&self) -> bool {
+        MidiStep::is_empty(self)
+    }
+}
+
+impl MidiStep {
+    /// Whether there is a note on this step
+    pub fn is_empty(&self) -> bool {
+        self.rest.len() <= 2
+    }
+
+    /// The 21 values of the step in the order they are stored, or None for those that aren't
+    /// set. Repeats read as 0 when unset, so a 0 is stored either way.
+    fn stored_values(&self) -> [Option<u16>; 21] {
+        let cc = |v: Option<u8>| v.map(u16::from);
+        let repeat = |v: u16| (v != 0).then_some(v);
+        [
+            Some(self.velocity as u16),
+            Some(self.note_length),
+            cc(self.cc74),
+            cc(self.cc71),
+            cc(self.cc13),
+            cc(self.cc12),
+            Some(self.note as u16),
+            cc(self.cc19),
+            cc(self.cc17),
+            Some(self.channel.into()),
+            Some(self.chord as u16),
+            None, // sample_end, unused
+            self.pitch_bend.map(|p| p as u16),
+            cc(self.cc22),
+            cc(self.cc75),
+            cc(self.program),
+            repeat(self.repeat_type),
+            repeat(self.repeat_grid),
+            Some(self.chance_type),
+            Some(self.chance_action),
+            Some(self.micro_move as u16),
+        ]
     }
 }
 
@@ -924,6 +1395,7 @@ impl fmt::Debug for MidiStep {
 }
 
 #[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MidiChannel {
     Jack(u8),
     Usb(u8),
@@ -938,3 +1410,12 @@ impl From<u16> for MidiChannel {
         }
     }
 }
+
+impl From<MidiChannel> for u16 {
+    fn from(channel: MidiChannel) -> Self {
+        match channel {
+            MidiChannel::Jack(n) => (n as u16).saturating_sub(1),
+            MidiChannel::Usb(n) => (n as u16).saturating_sub(1) + 16,
+        }
+    }
+}
@@ -0,0 +1,541 @@
+//! Three-way merge of projects.
+
+use std::fmt;
+
+use crate::diff::*;
+use crate::units::*;
+use crate::{CCMapping, MidiStep, Pattern, Project, Samples, Settings, Step, Track, TrackStep};
+
+/// A value that was changed differently in both projects being merged
+#[derive(PartialEq, Clone, Debug)]
+pub struct Conflict {
+    /// E.g. `pattern 3 audio track 2 variation 1 step 7`. Tracks and variations are numbered
+    /// from 0, as in the names of track files.
+    pub location: String,
+    pub field: String,
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} is {} in ours and {} in theirs (was {})",
+            self.location, self.field, self.ours, self.theirs, self.base
+        )
+    }
+}
+
+/// Combine the changes made to `base` in `ours` and in `theirs`.
+///
+/// Settings, sample slots, tracks and steps are merged field by field. Where both sides changed
+/// a value differently the value from `ours` is kept and a [Conflict] is reported, so the merged
+/// project can always be written.
+pub fn merge(base: &Project, ours: &Project, theirs: &Project) -> (Project, Vec<Conflict>) {
+    let mut merger = Merger::default();
+    let project = merger.project(base, ours, theirs);
+    (project, merger.conflicts)
+}
+
+fn debug<T: fmt::Debug>(value: &T) -> String {
+    format!("{:?}", value)
+}
+
+fn presence<T>(value: &Option<T>) -> String {
+    match value {
+        Some(_) => "present".to_string(),
+        None => "absent".to_string(),
+    }
+}
+
+/// Merge the named fields of `$out` from the same fields of `$base`, `$ours` and `$theirs`.
+/// Conflicting values are formatted as in diffs by the formatter after a field, or with `Debug`.
+macro_rules! merge_fields {
+    ($merger:expr, $location:expr, $out:expr, $base:expr, $ours:expr, $theirs:expr,
+     [$($field:ident $(: $format:expr)?),* $(,)?]) => {
+        $(
+            $out.$field = $merger.value(
+                $location,
+                stringify!($field),
+                &$base.$field,
+                &$ours.$field,
+                &$theirs.$field,
+                merge_fields!(@format $($format)?),
+            );
+        )*
+    };
+    (@format) => {
+        debug
+    };
+    (@format $format:expr) => {
+        |value| $format(value.clone())
+    };
+}
+
+#[derive(Default)]
+struct Merger {
+    conflicts: Vec<Conflict>,
+}
+
+impl Merger {
+    fn value<T: PartialEq + Clone>(
+        &mut self,
+        location: &str,
+        field: &str,
+        base: &T,
+        ours: &T,
+        theirs: &T,
+        format: impl Fn(&T) -> String,
+    ) -> T {
+        if ours == theirs || base == theirs {
+            ours.clone()
+        } else if base == ours {
+            theirs.clone()
+        } else {
+            self.conflicts.push(Conflict {
+                location: location.to_string(),
+                field: field.to_string(),
+                base: format(base),
+                ours: format(ours),
+                theirs: format(theirs),
+            });
+            ours.clone()
+        }
+    }
+
+    /// Merge a list of the samples element by element, or as a whole if its length was changed
+    fn samples_list<T: PartialEq + Clone + fmt::Debug>(
+        &mut self,
+        list_field: &str,
+        location: impl Fn(usize) -> String,
+        field: &str,
+        base: &[T],
+        ours: &[T],
+        theirs: &[T],
+    ) -> Vec<T> {
+        if base.len() != ours.len() || base.len() != theirs.len() {
+            let (base, ours, theirs) = (base.to_vec(), ours.to_vec(), theirs.to_vec());
+            return self.value("samples", list_field, &base, &ours, &theirs, debug);
+        }
+        (0..base.len())
+            .map(|i| self.value(&location(i), field, &base[i], &ours[i], &theirs[i], debug))
+            .collect()
+    }
+
+    fn project(&mut self, base: &Project, ours: &Project, theirs: &Project) -> Project {
+        let settings = self.settings(&base.settings, &ours.settings, &theirs.settings);
+        let samples = self.samples(&base.samples, &ours.samples, &theirs.samples);
+
+        let mut numbers: Vec<u8> = [base, ours, theirs]
+            .iter()
+            .flat_map(|p| p.patterns.iter().map(|p| p.number))
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        let mut patterns = vec![];
+        for number in numbers {
+            let location = format!("pattern {}", number);
+            let pattern = match (
+                base.pattern(number),
+                ours.pattern(number),
+                theirs.pattern(number),
+            ) {
+                (Some(b), Some(o), Some(t)) => Some(self.pattern(&location, b, o, t)),
+                (b, o, t) => self
+                    .value(&location, "pattern", &b, &o, &t, presence)
+                    .cloned(),
+            };
+            patterns.extend(pattern);
+        }
+
+        Project {
+            settings,
+            samples,
+            patterns,
+        }
+    }
+
+    fn settings(&mut self, base: &Settings, ours: &Settings, theirs: &Settings) -> Settings {
+        let mut settings = ours.clone();
+        merge_fields!(
+            self,
+            "settings",
+            settings,
+            base,
+            ours,
+            theirs,
+            [name, directory, bpm, x20, xa8, xb0, x90, rest]
+        );
+        for (kind, b, o, t, out) in [
+            (
+                "Jack",
+                &base.jack_cc_mapping,
+                &ours.jack_cc_mapping,
+                &theirs.jack_cc_mapping,
+                &mut settings.jack_cc_mapping,
+            ),
+            (
+                "USB",
+                &base.usb_cc_mapping,
+                &ours.usb_cc_mapping,
+                &theirs.usb_cc_mapping,
+                &mut settings.usb_cc_mapping,
+            ),
+        ] {
+            for (i, mapping) in out.iter_mut().enumerate() {
+                if let (Some(b), Some(t)) = (b.get(i), t.get(i)) {
+                    let location = format!("CC mapping for {} {}", kind, i + 1);
+                    *mapping = self.cc_mapping(&location, b, &o[i], t);
+                }
+            }
+        }
+        settings
+    }
+
+    fn cc_mapping(
+        &mut self,
+        location: &str,
+        base: &CCMapping,
+        ours: &CCMapping,
+        theirs: &CCMapping,
+    ) -> CCMapping {
+        let mut mapping = ours.clone();
+        merge_fields!(
+            self,
+            location,
+            mapping,
+            base,
+            ours,
+            theirs,
+            [
+                u_first_bytes,
+                cutoff: format_cc,
+                resonance: format_cc,
+                sample_attack: format_cc,
+                sample_decay: format_cc,
+                reverb_send: format_cc,
+                delay_send: format_cc,
+                overdrive: format_cc,
+                bit_depth: format_cc,
+            ]
+        );
+        mapping
+    }
+
+    fn samples(&mut self, base: &Samples, ours: &Samples, theirs: &Samples) -> Samples {
+        let mut samples = ours.clone();
+        samples.slot_folders = self.samples_list(
+            "slot_folders",
+            |i| format!("sample slot {}", i),
+            "folder",
+            &base.slot_folders,
+            &ours.slot_folders,
+            &theirs.slot_folders,
+        );
+        samples.folders = self.samples_list(
+            "folders",
+            |i| format!("sample folder {}", i),
+            "name",
+            &base.folders,
+            &ours.folders,
+            &theirs.folders,
+        );
+        merge_fields!(self, "samples", samples, base, ours, theirs, [x10, rest]);
+        samples
+    }
+
+    fn pattern(
+        &mut self,
+        location: &str,
+        base: &Pattern,
+        ours: &Pattern,
+        theirs: &Pattern,
+    ) -> Pattern {
+        let mut pattern = ours.clone();
+        for n in 0..8 {
+            pattern.audio_tracks[n] = self.variations(
+                &format!("{} audio track {}", location, n),
+                &base.audio_tracks[n],
+                &ours.audio_tracks[n],
+                &theirs.audio_tracks[n],
+                Self::step,
+            );
+            pattern.midi_tracks[n] = self.variations(
+                &format!("{} MIDI track {}", location, n),
+                &base.midi_tracks[n],
+                &ours.midi_tracks[n],
+                &theirs.midi_tracks[n],
+                Self::midi_step,
+            );
+        }
+        merge_fields!(self, location, pattern, base, ours, theirs, [rest]);
+        pattern
+    }
+
+    fn variations<S: TrackStep + Clone + PartialEq + fmt::Display>(
+        &mut self,
+        location: &str,
+        base: &[Option<Track<S>>; 16],
+        ours: &[Option<Track<S>>; 16],
+        theirs: &[Option<Track<S>>; 16],
+        merge_step: fn(&mut Self, &str, &S, &S, &S) -> S,
+    ) -> [Option<Track<S>>; 16] {
+        let active = |variations: &[Option<Track<S>>; 16]| {
+            variations
+                .iter()
+                .flatten()
+                .find(|t| t.is_default)
+                .map(|t| t.variation)
+        };
+        let option = |v: &Option<usize>| v.map(|v| v.to_string()).unwrap_or_default();
+        let actives = (active(base), active(ours), active(theirs));
+        let active = self.value(
+            location,
+            "active variation",
+            &actives.0,
+            &actives.1,
+            &actives.2,
+            option,
+        );
+
+        // Which variation is active is merged above, so it doesn't make the tracks differ
+        let inactive = |track: &Option<Track<S>>| {
+            track.clone().map(|mut t| {
+                t.is_default = false;
+                t
+            })
+        };
+        let mut variations: [Option<Track<S>>; 16] = Default::default();
+        for (v, merged) in variations.iter_mut().enumerate() {
+            let location = format!("{} variation {}", location, v);
+            let (b, o, t) = (inactive(&base[v]), inactive(&ours[v]), inactive(&theirs[v]));
+            *merged = match (&b, &o, &t) {
+                (Some(b), Some(o), Some(t)) => Some(self.track(&location, b, o, t, merge_step)),
+                _ => self.value(&location, "variation", &b, &o, &t, presence),
+            };
+        }
+
+        // One side may have activated a variation the other removed
+        let present = |v: &usize| variations[*v].is_some();
+        let active = active.filter(present).or_else(|| {
+            if active.is_some() {
+                self.conflicts.push(Conflict {
+                    location: location.to_string(),
+                    field: "active variation".to_string(),
+                    base: option(&actives.0),
+                    ours: option(&actives.1),
+                    theirs: option(&actives.2),
+                });
+            }
+            actives.1.filter(present).or_else(|| (0..16).find(present))
+        });
+        if let Some(v) = active {
+            variations[v].as_mut().unwrap().is_default = true;
+        }
+        variations
+    }
+
+    fn track<S: TrackStep + Clone + PartialEq + fmt::Display>(
+        &mut self,
+        location: &str,
+        base: &Track<S>,
+        ours: &Track<S>,
+        theirs: &Track<S>,
+        merge_step: fn(&mut Self, &str, &S, &S, &S) -> S,
+    ) -> Track<S> {
+        let mut track = ours.clone();
+        merge_fields!(
+            self,
+            location,
+            track,
+            base,
+            ours,
+            theirs,
+            [
+                swing: format_swing,
+                play_mode: format_play_mode,
+                track_speed: format_track_speed,
+            ]
+        );
+        let num_steps = self.value(
+            location,
+            "num_steps",
+            &base.steps.len(),
+            &ours.steps.len(),
+            &theirs.steps.len(),
+            |n| n.to_string(),
+        );
+
+        let len = base
+            .steps
+            .len()
+            .max(ours.steps.len())
+            .max(theirs.steps.len());
+        let mut steps = vec![];
+        for i in 0..len {
+            let location = format!("{} step {}", location, i);
+            let (b, o, t) = (base.steps.get(i), ours.steps.get(i), theirs.steps.get(i));
+            let step = match (b, o, t) {
+                (Some(b), Some(o), Some(t)) => Some(merge_step(self, &location, b, o, t)),
+                // Steps past the end of a track were added or removed with num_steps
+                _ => self
+                    .value(&location, "step", &b, &o, &t, |s| {
+                        s.map(step_text).unwrap_or_else(|| "absent".to_string())
+                    })
+                    .cloned(),
+            };
+            match step {
+                Some(step) => steps.push(step),
+                None => break,
+            }
+        }
+        steps.truncate(num_steps);
+        track.steps = steps;
+        track
+    }
+
+    /// If a note was added to or removed from a step, merge the whole step. Otherwise merge
+    /// the values of the step.
+    fn whole_step<S: TrackStep + Clone + PartialEq + fmt::Display>(
+        &mut self,
+        location: &str,
+        base: &S,
+        ours: &S,
+        theirs: &S,
+    ) -> Option<S> {
+        let notes = [base.is_empty(), ours.is_empty(), theirs.is_empty()];
+        if notes.iter().all(|&n| n == notes[0]) {
+            return None;
+        }
+        Some(self.value(location, "step", base, ours, theirs, step_text))
+    }
+
+    fn step(&mut self, location: &str, base: &Step, ours: &Step, theirs: &Step) -> Step {
+        if let Some(step) = self.whole_step(location, base, ours, theirs) {
+            return step;
+        }
+        let mut step = ours.clone();
+        merge_fields!(
+            self,
+            location,
+            step,
+            base,
+            ours,
+            theirs,
+            [
+                sample: format_sample,
+                note: note_name,
+                volume: format_volume,
+                pan: format_pan,
+                filter_cutoff: format_filter,
+                filter_resonance: percent_of,
+                overdrive: percent_of,
+                bit_depth,
+                micro_move: format_micro_move,
+                reverb: percent_of,
+                delay: percent_of,
+                sample_start,
+                sample_end,
+                sample_attack: percent_of,
+                sample_decay: percent_of,
+                sample_folder,
+                repeat_type: repeat_type_name,
+                repeat_grid: repeat_grid_name,
+                chance_type: chance_type_name,
+                chance_action: chance_action_name,
+                micro_tune: format_cents,
+                rest,
+            ]
+        );
+        step
+    }
+
+    fn midi_step(
+        &mut self,
+        location: &str,
+        base: &MidiStep,
+        ours: &MidiStep,
+        theirs: &MidiStep,
+    ) -> MidiStep {
+        if let Some(step) = self.whole_step(location, base, ours, theirs) {
+            return step;
+        }
+        let mut step = ours.clone();
+        merge_fields!(
+            self,
+            location,
+            step,
+            base,
+            ours,
+            theirs,
+            [
+                channel: format_midi_channel,
+                program: option,
+                note: note_name,
+                velocity,
+                note_length: format_note_length,
+                chord: chord_name,
+                micro_move: format_micro_move,
+                pitch_bend: format_pitch_bend,
+                cc12: option,
+                cc13: option,
+                cc17: option,
+                cc19: option,
+                cc22: option,
+                cc71: option,
+                cc74: option,
+                cc75: option,
+                repeat_type: repeat_type_name,
+                repeat_grid: repeat_grid_name,
+                chance_type: chance_type_name,
+                chance_action: chance_action_name,
+            ]
+        );
+        step.values = self.midi_values(location, base, ours, theirs);
+
+        // Bytes 5-7 of the rest are the bitmask of the values that are set, which is written
+        // from the merged values
+        if [base, ours, theirs].iter().all(|s| s.rest.len() >= 8) {
+            let unknown = |s: &MidiStep| [&s.rest[..5], &s.rest[8..]].concat();
+            let rest = self.value(
+                location,
+                "rest",
+                &unknown(base),
+                &unknown(ours),
+                &unknown(theirs),
+                debug,
+            );
+            step.rest = [&rest[..5], &ours.rest[5..8], &rest[5..]].concat();
+        } else {
+            merge_fields!(self, location, step, base, ours, theirs, [rest]);
+        }
+        step
+    }
+
+    /// Merge the stored values of the parameters that aren't set one by one, as setting a
+    /// parameter clears its value
+    fn midi_values(
+        &mut self,
+        location: &str,
+        base: &MidiStep,
+        ours: &MidiStep,
+        theirs: &MidiStep,
+    ) -> Vec<u8> {
+        let value = |s: &MidiStep, i: usize| {
+            s.values
+                .get(i * 2..i * 2 + 2)
+                .map_or(vec![0, 0], |v| v.to_vec())
+        };
+        let mut values = vec![];
+        for i in 0..21 {
+            let (b, o, t) = (value(base, i), value(ours, i), value(theirs, i));
+            values.extend(self.value(location, "values", &b, &o, &t, debug));
+        }
+        if values.iter().all(|&b| b == 0) {
+            values.clear();
+        }
+        values
+    }
+}
@@ -0,0 +1,57 @@
+//! Parsing the pattern and track files of a project in parallel.
+
+use rayon::prelude::*;
+
+use crate::vfs::{
+    collect_all, in_file, join, list_dir, pattern_files, read_file, read_variation, track_files,
+};
+use crate::{Pattern, Project, Result, Samples, Settings, Vfs};
+
+impl Pattern {
+    /// As [read_all](Self::read_all), parsing the pattern and track files in parallel
+    pub(crate) fn read_all_par(files: &(impl Vfs + Sync + ?Sized), dir: &str) -> Result<Vec<Self>> {
+        let names = list_dir(files, dir)?;
+        // Collecting keeps the order of the files, whichever finishes first
+        let patterns: Vec<Result<Self>> = pattern_files(&names)
+            .into_par_iter()
+            .map(|(name, number)| {
+                let (pattern, variations) = rayon::join(
+                    || {
+                        let path = join(dir, name);
+                        in_file(Self::from_bytes(&read_file(files, &path)?, number), &path)
+                    },
+                    || {
+                        let variations: Vec<_> = track_files(&names, number)
+                            .into_par_iter()
+                            .map(|track_file| read_variation(files, dir, track_file))
+                            .collect();
+                        collect_all(variations)
+                    },
+                );
+                let mut pattern = pattern?;
+                pattern.add_variation_files(variations?);
+                Ok(pattern)
+            })
+            .collect();
+        collect_all(patterns)
+    }
+}
+
+impl Project {
+    /// Read a project from its files, wherever they are stored, parsing its pattern and track
+    /// files in parallel.
+    ///
+    /// The patterns are in the same order as with [from_files](Self::from_files). When files
+    /// can't be read, the error describes all of them.
+    pub fn from_files_par(files: &(impl Vfs + Sync + ?Sized)) -> Result<Self> {
+        let settings = Settings::from_bytes(&read_file(files, "settings")?)?;
+        let samples = Samples::from_bytes(&read_file(files, "samples/samplesMetadata")?)?;
+        let patterns = Pattern::read_all_par(files, "patterns")?;
+
+        Ok(Self {
+            settings,
+            samples,
+            patterns,
+        })
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{Track, TrackStep};
+
+/// The order in which a track's steps are visited.
+///
+/// Values are taken from the order the modes are listed on the device: Forward is the first
+/// option (0), Reverse the second (1) and Thumper the sixth (5). The values of PingPong,
+/// Pendulum and Random have not yet been confirmed against a saved project, and neither has
+/// the order in which they play steps: see [is_approximate](Self::is_approximate).
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PlayMode {
+    /// 0, 1, 2, ... n-1
+    Forward,
+    /// n-1, n-2, ... 0
+    Reverse,
+    /// Forward then back again without repeating the end steps: 0, 1, ... n-1, n-2, ... 1.
+    /// Approximate.
+    PingPong,
+    /// Forward then back again, repeating the end steps: 0, 1, ... n-1, n-1, ... 0.
+    /// Approximate.
+    Pendulum,
+    /// A (deterministic) random step for every position. Approximate: the device's random
+    /// order can't be reproduced.
+    Random,
+    /// The first step is played between every other step: 0, 1, 0, 2, ... 0, n-1
+    Thumper,
+    Unknown(u8),
+}
+
+impl From<u8> for PlayMode {
+    fn from(x: u8) -> Self {
+        match x {
+            0 => PlayMode::Forward,
+            1 => PlayMode::Reverse,
+            2 => PlayMode::PingPong,
+            3 => PlayMode::Pendulum,
+            4 => PlayMode::Random,
+            5 => PlayMode::Thumper,
+            x => PlayMode::Unknown(x),
+        }
+    }
+}
+
+impl From<PlayMode> for u8 {
+    fn from(mode: PlayMode) -> Self {
+        match mode {
+            PlayMode::Forward => 0,
+            PlayMode::Reverse => 1,
+            PlayMode::PingPong => 2,
+            PlayMode::Pendulum => 3,
+            PlayMode::Random => 4,
+            PlayMode::Thumper => 5,
+            PlayMode::Unknown(x) => x,
+        }
+    }
+}
+
+impl PlayMode {
+    /// Whether [step_at](Self::step_at) is a guess at the order the device plays steps in,
+    /// rather than one checked against it
+    pub fn is_approximate(self) -> bool {
+        matches!(
+            self,
+            PlayMode::PingPong | PlayMode::Pendulum | PlayMode::Random | PlayMode::Unknown(_)
+        )
+    }
+
+    /// The step index played at `position`, counting from the start of playback, for a track
+    /// of `len` steps. Approximate for some modes: see [is_approximate](Self::is_approximate).
+    pub fn step_at(self, position: usize, len: usize) -> usize {
+        if len <= 1 {
+            return 0;
+        }
+        match self {
+            PlayMode::Forward | PlayMode::Unknown(_) => position % len,
+            PlayMode::Reverse => len - 1 - position % len,
+            PlayMode::PingPong => {
+                let p = position % (2 * len - 2);
+                if p < len {
+                    p
+                } else {
+                    2 * len - 2 - p
+                }
+            }
+            PlayMode::Pendulum => {
+                let p = position % (2 * len);
+                if p < len {
+                    p
+                } else {
+                    2 * len - 1 - p
+                }
+            }
+            PlayMode::Random => (split_mix(position as u64) % len as u64) as usize,
+            PlayMode::Thumper => {
+                let p = position % (2 * len - 2);
+                if p.is_multiple_of(2) {
+                    0
+                } else {
+                    p / 2 + 1
+                }
+            }
+        }
+    }
+}
+
+/// A small, stable hash so that `Random` play order is reproducible between runs.
+fn split_mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+impl<S: TrackStep + Clone> Track<S> {
+    /// The indices of the steps played during loop number `loop_index`.
+    ///
+    /// A loop is always `steps.len()` steps long, so modes with a longer cycle (e.g. PingPong)
+    /// continue from where the previous loop left off.
+    pub fn play_order(&self, loop_index: usize) -> Vec<usize> {
+        let mode = PlayMode::from(self.play_mode);
+        let len = self.steps.len();
+        (loop_index * len..(loop_index + 1) * len)
+            .map(|position| mode.step_at(position, len))
+            .collect()
+    }
+
+    /// The steps played during loop number `loop_index`, in the order they are played.
+    pub fn play_steps(&self, loop_index: usize) -> impl Iterator<Item = &S> + '_ {
+        self.play_order(loop_index)
+            .into_iter()
+            .map(move |i| &self.steps[i])
+    }
+}
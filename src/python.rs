@@ -0,0 +1,352 @@
+//! Python bindings, for analysing projects in notebooks. Built with `maturin`, which uses the
+//! settings in `pyproject.toml`.
+//!
+//! The classes are read-only copies of the Rust data. `Project.to_records()` flattens every step
+//! into a dict, e.g. for `pandas.DataFrame`.
+
+use std::path::PathBuf;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::units::{format_midi_channel, format_track_speed};
+use crate::{MidiStep, Pattern, PlayMode, Project, Settings, Step, Track, TrackStep};
+
+create_exception!(play_files, ParseError, PyException);
+
+impl From<crate::ParseError> for PyErr {
+    fn from(e: crate::ParseError) -> Self {
+        ParseError::new_err(e.0)
+    }
+}
+
+/// Define a read-only Python class with an attribute for each field, copied from a Rust value
+macro_rules! py_class {
+    ($(#[$doc:meta])* $py:ident($name:literal) from $rust:ty as $s:ident {
+        $($field:ident: $ty:ty = $value:expr),* $(,)?
+    }) => {
+        $(#[$doc])*
+        #[pyclass(name = $name, module = "play_files", frozen, get_all, skip_from_py_object)]
+        #[derive(Clone)]
+        pub struct $py {
+            $($field: $ty),*
+        }
+
+        impl From<&$rust> for $py {
+            fn from($s: &$rust) -> Self {
+                Self {
+                    $($field: $value),*
+                }
+            }
+        }
+
+        impl $py {
+            /// Add each attribute to a record of `to_records`
+            fn add_to(&self, record: &Bound<'_, PyDict>) -> PyResult<()> {
+                $(record.set_item(stringify!($field), self.$field.clone())?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+#[pyclass(
+    name = "Settings",
+    module = "play_files",
+    frozen,
+    get_all,
+    skip_from_py_object
+)]
+#[derive(Clone)]
+pub struct PySettings {
+    name: String,
+    directory: String,
+    bpm: f32,
+}
+
+impl From<&Settings> for PySettings {
+    fn from(s: &Settings) -> Self {
+        Self {
+            name: s.name.clone(),
+            directory: s.directory.clone(),
+            bpm: s.bpm,
+        }
+    }
+}
+
+py_class! {
+    /// A step of an audio track, with its values as they are stored
+    PyStep("Step") from Step as s {
+        number: usize = s.number,
+        is_empty: bool = s.is_empty(),
+        sample: u16 = s.sample,
+        note: u8 = s.note,
+        volume: u16 = s.volume,
+        pan: i16 = s.pan,
+        filter_cutoff: i16 = s.filter_cutoff,
+        filter_resonance: u16 = s.filter_resonance,
+        overdrive: u16 = s.overdrive,
+        bit_depth: u8 = s.bit_depth,
+        micro_move: i16 = s.micro_move,
+        micro_tune: i16 = s.micro_tune,
+        reverb: i16 = s.reverb,
+        delay: i16 = s.delay,
+        sample_start: i16 = s.sample_start,
+        sample_end: i16 = s.sample_end,
+        sample_attack: u16 = s.sample_attack,
+        sample_decay: u16 = s.sample_decay,
+        sample_folder: u16 = s.sample_folder,
+        repeat_type: u16 = s.repeat_type,
+        repeat_grid: u16 = s.repeat_grid,
+        chance_type: u16 = s.chance_type,
+        chance_action: u16 = s.chance_action,
+    }
+}
+
+py_class! {
+    /// A step of a MIDI track. Values that the step doesn't send are None.
+    PyMidiStep("MidiStep") from MidiStep as s {
+        number: usize = s.number,
+        is_empty: bool = s.is_empty(),
+        channel: String = format_midi_channel(s.channel),
+        program: Option<u8> = s.program,
+        note: u8 = s.note,
+        velocity: u8 = s.velocity,
+        note_length: u16 = s.note_length,
+        chord: i16 = s.chord,
+        micro_move: i16 = s.micro_move,
+        pitch_bend: Option<i16> = s.pitch_bend,
+        cc12: Option<u8> = s.cc12,
+        cc13: Option<u8> = s.cc13,
+        cc17: Option<u8> = s.cc17,
+        cc19: Option<u8> = s.cc19,
+        cc22: Option<u8> = s.cc22,
+        cc71: Option<u8> = s.cc71,
+        cc74: Option<u8> = s.cc74,
+        cc75: Option<u8> = s.cc75,
+        repeat_type: u16 = s.repeat_type,
+        repeat_grid: u16 = s.repeat_grid,
+        chance_type: u16 = s.chance_type,
+        chance_action: u16 = s.chance_action,
+    }
+}
+
+#[derive(Clone)]
+enum Steps {
+    Audio(Vec<PyStep>),
+    Midi(Vec<PyMidiStep>),
+}
+
+/// A variation of an audio or MIDI track
+#[pyclass(name = "Track", module = "play_files", frozen, skip_from_py_object)]
+#[derive(Clone)]
+pub struct PyTrack {
+    /// The track number, from 0 for both audio and MIDI tracks
+    #[pyo3(get)]
+    number: usize,
+    #[pyo3(get)]
+    variation: usize,
+    #[pyo3(get)]
+    is_midi: bool,
+    /// Whether this variation was active when the pattern was saved
+    #[pyo3(get)]
+    is_active: bool,
+    #[pyo3(get)]
+    swing: u8,
+    /// E.g. `Forward` or `Thumper`
+    #[pyo3(get)]
+    play_mode: String,
+    /// E.g. `1/1` or `Paused`
+    #[pyo3(get)]
+    track_speed: String,
+    steps: Steps,
+}
+
+impl<S: TrackStep + Clone> Track<S> {
+    fn to_py(&self, steps: Steps) -> PyTrack {
+        PyTrack {
+            number: self.number,
+            variation: self.variation,
+            is_midi: matches!(steps, Steps::Midi(_)),
+            is_active: self.is_default,
+            swing: self.swing,
+            play_mode: format!("{:?}", PlayMode::from(self.play_mode)),
+            track_speed: format_track_speed(self.track_speed),
+            steps,
+        }
+    }
+}
+
+impl From<&Track<Step>> for PyTrack {
+    fn from(t: &Track<Step>) -> Self {
+        t.to_py(Steps::Audio(t.steps.iter().map(PyStep::from).collect()))
+    }
+}
+
+impl From<&Track<MidiStep>> for PyTrack {
+    fn from(t: &Track<MidiStep>) -> Self {
+        t.to_py(Steps::Midi(t.steps.iter().map(PyMidiStep::from).collect()))
+    }
+}
+
+#[pymethods]
+impl PyTrack {
+    /// The steps of the track: `Step`s for audio tracks and `MidiStep`s for MIDI tracks
+    #[getter]
+    fn steps<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        match &self.steps {
+            Steps::Audio(steps) => PyList::new(py, steps.clone()),
+            Steps::Midi(steps) => PyList::new(py, steps.clone()),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<{} track {} variation {}>",
+            if self.is_midi { "MIDI" } else { "Audio" },
+            self.number,
+            self.variation
+        )
+    }
+}
+
+impl PyTrack {
+    /// A record for each step, with the pattern and track it is on
+    fn add_records(&self, pattern: u8, records: &Bound<'_, PyList>) -> PyResult<()> {
+        let py = records.py();
+        let add = |add_step: &dyn Fn(&Bound<'_, PyDict>) -> PyResult<()>| -> PyResult<()> {
+            let record = PyDict::new(py);
+            record.set_item("pattern", pattern)?;
+            record.set_item("kind", if self.is_midi { "midi" } else { "audio" })?;
+            record.set_item("track", self.number)?;
+            record.set_item("variation", self.variation)?;
+            record.set_item("is_active", self.is_active)?;
+            add_step(&record)?;
+            records.append(record)
+        };
+        match &self.steps {
+            Steps::Audio(steps) => steps.iter().try_for_each(|s| add(&|r| s.add_to(r))),
+            Steps::Midi(steps) => steps.iter().try_for_each(|s| add(&|r| s.add_to(r))),
+        }
+    }
+}
+
+#[pyclass(name = "Pattern", module = "play_files", frozen, skip_from_py_object)]
+#[derive(Clone)]
+pub struct PyPattern {
+    #[pyo3(get)]
+    number: u8,
+    /// Every variation of every track, audio tracks first
+    #[pyo3(get)]
+    variations: Vec<PyTrack>,
+}
+
+impl From<&Pattern> for PyPattern {
+    fn from(p: &Pattern) -> Self {
+        let audio = p.audio_tracks.iter().flatten().flatten().map(PyTrack::from);
+        let midi = p.midi_tracks.iter().flatten().flatten().map(PyTrack::from);
+        Self {
+            number: p.number,
+            variations: audio.chain(midi).collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyPattern {
+    /// The active variation of each audio track
+    #[getter]
+    fn audio_tracks(&self) -> Vec<PyTrack> {
+        self.active(false)
+    }
+
+    /// The active variation of each MIDI track
+    #[getter]
+    fn midi_tracks(&self) -> Vec<PyTrack> {
+        self.active(true)
+    }
+
+    /// Every step of every variation, as a dict
+    fn to_records<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let records = PyList::empty(py);
+        for track in &self.variations {
+            track.add_records(self.number, &records)?;
+        }
+        Ok(records)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Pattern {}>", self.number)
+    }
+}
+
+impl PyPattern {
+    fn active(&self, is_midi: bool) -> Vec<PyTrack> {
+        self.variations
+            .iter()
+            .filter(|t| t.is_midi == is_midi && t.is_active)
+            .cloned()
+            .collect()
+    }
+}
+
+#[pyclass(name = "Project", module = "play_files", frozen, skip_from_py_object)]
+pub struct PyProject {
+    #[pyo3(get)]
+    settings: PySettings,
+    #[pyo3(get)]
+    patterns: Vec<PyPattern>,
+}
+
+impl From<&Project> for PyProject {
+    fn from(p: &Project) -> Self {
+        Self {
+            settings: PySettings::from(&p.settings),
+            patterns: p.patterns.iter().map(PyPattern::from).collect(),
+        }
+    }
+}
+
+#[pymethods]
+impl PyProject {
+    /// Read the project in the directory `path`
+    #[staticmethod]
+    fn read(path: PathBuf) -> PyResult<Self> {
+        Ok(Self::from(&Project::read(&path)?))
+    }
+
+    /// Get a pattern by its number
+    fn pattern(&self, number: u8) -> Option<PyPattern> {
+        self.patterns.iter().find(|p| p.number == number).cloned()
+    }
+
+    /// Every step of every variation of every pattern, as a dict with the pattern, kind
+    /// (`audio` or `midi`), track and variation it is on, and the values of the step
+    fn to_records<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let records = PyList::empty(py);
+        for pattern in &self.patterns {
+            for track in &pattern.variations {
+                track.add_records(pattern.number, &records)?;
+            }
+        }
+        Ok(records)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Project {:?}>", self.settings.name)
+    }
+}
+
+#[pymodule]
+fn play_files(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyProject>()?;
+    m.add_class::<PySettings>()?;
+    m.add_class::<PyPattern>()?;
+    m.add_class::<PyTrack>()?;
+    m.add_class::<PyStep>()?;
+    m.add_class::<PyMidiStep>()?;
+    m.add("ParseError", m.py().get_type::<ParseError>())?;
+    Ok(())
+}
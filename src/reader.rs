@@ -1,83 +1,133 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::fmt;
 
-pub struct Reader {
-    buffer: Vec<u8>,
-    position: Rc<RefCell<usize>>,
+use crate::{ParseError, Result};
+
+/// A cursor over the bytes of a file. Reading past the end of the file is an error.
+pub struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
 }
 
 #[allow(dead_code)]
-impl Reader {
-    pub fn new(buffer: Vec<u8>) -> Self {
+impl<'a> Reader<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
         Self {
             buffer,
-            position: Rc::new(RefCell::new(0)),
+            position: 0,
         }
     }
 
-    pub fn read(&self) -> u8 {
-        let p: usize = *self.position.borrow();
-        let b = self.buffer[p];
-        *self.position.borrow_mut() += 1;
-        b
+    #[cold]
+    fn end_of_file(&self, n: usize) -> ParseError {
+        ParseError(format!(
+            "Unexpected end of file: {} bytes at byte {} of {}",
+            n,
+            self.position,
+            self.buffer.len()
+        ))
+    }
+
+    #[inline]
+    pub fn read(&mut self) -> Result<u8> {
+        let b = *self
+            .buffer
+            .get(self.position)
+            .ok_or_else(|| self.end_of_file(1))?;
+        self.position += 1;
+        Ok(b)
+    }
+
+    #[inline]
+    pub fn read_bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bs = self
+            .buffer
+            .get(self.position..)
+            .and_then(|rest| rest.get(..n))
+            .ok_or_else(|| self.end_of_file(n))?;
+        self.position += n;
+        Ok(bs)
     }
 
-    pub fn read_bytes(&self, n: usize) -> &[u8] {
-        let p: usize = *self.position.borrow();
-        let bs = &self.buffer[p..p + n];
-        *self.position.borrow_mut() += n;
-        bs
+    /// Read a byte, which must be `tag`. `what` is only formatted when it isn't.
+    #[inline]
+    pub fn read_tag(&mut self, tag: u8, what: impl fmt::Display) -> Result<()> {
+        match self.read()? {
+            t if t == tag => Ok(()),
+            t => Err(self.wrong_tag(tag, t, &what)),
+        }
+    }
+
+    #[cold]
+    fn wrong_tag(&self, expected: u8, found: u8, what: &dyn fmt::Display) -> ParseError {
+        ParseError(format!(
+            "Expected tag {:02X} of {} at byte {}, found {:02X}",
+            expected,
+            what,
+            self.position - 1,
+            found
+        ))
     }
 
-    pub fn read_bool(&self) -> bool {
-        self.read() == 1
+    pub fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.read()? == 1)
     }
 
-    pub fn read_string(&self, n: usize) -> String {
-        let b = self.read_bytes(n);
+    pub fn read_string(&mut self, n: usize) -> Result<String> {
+        let pos = self.position;
+        let b = self.read_bytes(n)?;
         std::str::from_utf8(b)
-            .expect("invalid utf-8 sequence in string")
-            .to_string()
+            .map(|s| s.to_string())
+            .map_err(|_| ParseError(format!("Invalid UTF-8 in string at byte {}", pos)))
+    }
+
+    /// A string preceded by its length in one byte
+    pub fn read_short_string(&mut self) -> Result<String> {
+        let n = self.read()? as usize;
+        self.read_string(n)
     }
 
-    pub fn read_variable_quantity(&self) -> usize {
+    pub fn read_variable_quantity(&mut self) -> Result<usize> {
+        let pos = self.position;
         let mut bytes: [u8; 4] = [0; 4];
-        for i in 0..4 {
-            let b = self.read();
-            bytes[i] = b & 0b01111111;
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let b = self.read()?;
+            *byte = b & 0b01111111;
             if b & 0b10000000 == 0 {
                 break;
             }
             // If we're in our last loop, we shouldn't make it this far:
             if i == 3 {
-                panic!("More bytes than expected in a variable quantity")
+                return Err(ParseError(format!(
+                    "More bytes than expected in a variable quantity at byte {}",
+                    pos
+                )));
             }
         }
 
-        bytes
+        Ok(bytes
             .iter()
             .enumerate()
-            .fold(0, |r, (i, &b)| r + ((b as usize) << (i * 7)))
+            .fold(0, |r, (i, &b)| r + ((b as usize) << (i * 7))))
     }
 
     pub fn pos(&self) -> usize {
-        *self.position.borrow()
+        self.position
     }
 
-    pub fn set_pos(&self, n: usize) {
-        *self.position.borrow_mut() = n;
+    pub fn set_pos(&mut self, n: usize) {
+        self.position = n;
     }
 
-    pub fn step_back(&self) {
-        *self.position.borrow_mut() -= 1;
+    pub fn step_back(&mut self) {
+        self.position -= 1;
     }
 
     pub fn buffer_len(&self) -> usize {
         self.buffer.len()
     }
 
-    pub fn rest(&self) -> Vec<u8> {
-        let p: usize = *self.position.borrow();
-        self.buffer[p..].to_vec()
+    /// The bytes that haven't been read
+    pub fn rest(&self) -> &'a [u8] {
+        self.buffer.get(self.position..).unwrap_or(&[])
     }
 }
@@ -0,0 +1,161 @@
+//! The global reverb and delay send effects.
+
+use super::AudioBuffer;
+
+/// Settings for the reverb bus
+///
+/// The reverb parameters stored in a project's settings have not been decoded yet, so these
+/// have to be supplied by the user.
+#[derive(PartialEq, Clone, Debug)]
+pub struct ReverbOptions {
+    /// 0.0-1.0
+    pub size: f32,
+    /// High frequency damping of the reverb tail, 0.0-1.0
+    pub damping: f32,
+    /// Gain of the reverb return
+    pub level: f32,
+}
+
+impl Default for ReverbOptions {
+    fn default() -> Self {
+        Self {
+            size: 0.8,
+            damping: 0.5,
+            level: 1.0,
+        }
+    }
+}
+
+/// Settings for the delay bus
+///
+/// Like the reverb, the delay parameters stored in a project's settings are not known yet.
+#[derive(PartialEq, Clone, Debug)]
+pub struct DelayOptions {
+    /// Delay time in steps (16th notes), synced to the project's bpm
+    pub steps: f32,
+    /// 0.0-1.0
+    pub feedback: f32,
+    /// Gain of the delay return
+    pub level: f32,
+}
+
+impl Default for DelayOptions {
+    fn default() -> Self {
+        Self {
+            // A dotted eighth
+            steps: 3.0,
+            feedback: 0.4,
+            level: 1.0,
+        }
+    }
+}
+
+struct Comb {
+    buffer: Vec<f32>,
+    pos: usize,
+    store: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            pos: 0,
+            store: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f32, feedback: f32, damping: f32) -> f32 {
+        let y = self.buffer[self.pos];
+        self.store = y * (1.0 - damping) + self.store * damping;
+        self.buffer[self.pos] = x + self.store * feedback;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        y
+    }
+}
+
+struct AllPass {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl AllPass {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let delayed = self.buffer[self.pos];
+        self.buffer[self.pos] = x + delayed * 0.5;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        delayed - x
+    }
+}
+
+// Freeverb's tunings, in samples at 44.1kHz
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+const STEREO_SPREAD: usize = 23;
+
+/// Run a stereo send through a Freeverb-style reverb, returning the wet signal only
+pub(crate) fn reverb(send: &AudioBuffer, options: &ReverbOptions) -> AudioBuffer {
+    let scale = |n: usize| n * send.sample_rate as usize / 44100;
+    let feedback = 0.7 + 0.28 * options.size.clamp(0.0, 1.0);
+    let damping = 0.4 * options.damping.clamp(0.0, 1.0);
+
+    let mut out = AudioBuffer::new(send.sample_rate, 2, send.frames());
+    for (c, channel) in out.channels.iter_mut().enumerate() {
+        let spread = c * STEREO_SPREAD;
+        let mut combs: Vec<Comb> = COMB_TUNINGS
+            .iter()
+            .map(|&n| Comb::new(scale(n + spread)))
+            .collect();
+        let mut allpasses: Vec<AllPass> = ALLPASS_TUNINGS
+            .iter()
+            .map(|&n| AllPass::new(scale(n + spread)))
+            .collect();
+
+        for (i, y) in channel.iter_mut().enumerate() {
+            // Both channels feed the reverb
+            let (l, r) = send.frame(i);
+            let x = (l + r) * 0.015;
+            let mut acc = combs
+                .iter_mut()
+                .map(|comb| comb.process(x, feedback, damping))
+                .sum();
+            for allpass in allpasses.iter_mut() {
+                acc = allpass.process(acc);
+            }
+            *y = acc * options.level;
+        }
+    }
+    out
+}
+
+/// Run a stereo send through a feedback delay, returning the wet signal only
+pub(crate) fn delay(send: &AudioBuffer, bpm: f32, options: &DelayOptions) -> AudioBuffer {
+    let step = 60.0 / bpm / 4.0;
+    let len = ((options.steps * step * send.sample_rate as f32) as usize).max(1);
+    let feedback = options.feedback.clamp(0.0, 0.95);
+
+    let mut out = AudioBuffer::new(send.sample_rate, 2, send.frames());
+    for (c, channel) in out.channels.iter_mut().enumerate() {
+        let mut buffer = vec![0.0; len];
+        let mut pos = 0;
+        for (i, y) in channel.iter_mut().enumerate() {
+            let x = if c == 0 {
+                send.frame(i).0
+            } else {
+                send.frame(i).1
+            };
+            let delayed = buffer[pos];
+            buffer[pos] = x + delayed * feedback;
+            pos = (pos + 1) % len;
+            *y = delayed * options.level;
+        }
+    }
+    out
+}
@@ -0,0 +1,99 @@
+//! The per-step effects of an audio track: overdrive, bit crusher and filter, in that order.
+
+use std::f32::consts::PI;
+
+use crate::Step;
+
+/// A topology-preserving-transform state variable filter, which stays stable under modulation
+/// and at high resonance
+#[derive(Clone, Debug)]
+struct Filter {
+    highpass: bool,
+    g: f32,
+    k: f32,
+    // Integrator state for the left and right channels
+    ic1: [f32; 2],
+    ic2: [f32; 2],
+}
+
+impl Filter {
+    /// `cutoff` is -10000 (LP100) to 10000 (HP100). 0 is off.
+    fn new(cutoff: i16, resonance: u16, sample_rate: u32) -> Option<Self> {
+        if cutoff == 0 {
+            return None;
+        }
+        let amount = (cutoff as f32 / 10000.0).abs().min(1.0);
+        let highpass = cutoff > 0;
+        // An exponential sweep over 20Hz-20kHz: the low-pass closes and the high-pass opens as
+        // the amount increases
+        let freq = if highpass {
+            20.0 * 1000f32.powf(amount)
+        } else {
+            20.0 * 1000f32.powf(1.0 - amount)
+        }
+        .min(sample_rate as f32 * 0.45);
+        // Resonance maps to a Q of 0.5 to 20
+        let q = 0.5 * 40f32.powf(resonance.min(10000) as f32 / 10000.0);
+
+        Some(Self {
+            highpass,
+            g: (PI * freq / sample_rate as f32).tan(),
+            k: 1.0 / q,
+            ic1: [0.0; 2],
+            ic2: [0.0; 2],
+        })
+    }
+
+    fn process(&mut self, channel: usize, x: f32) -> f32 {
+        let (g, k) = (self.g, self.k);
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let v3 = x - self.ic2[channel];
+        let v1 = a1 * self.ic1[channel] + g * a1 * v3;
+        let v2 = self.ic2[channel] + g * v1;
+        self.ic1[channel] = 2.0 * v1 - self.ic1[channel];
+        self.ic2[channel] = 2.0 * v2 - self.ic2[channel];
+
+        if self.highpass {
+            x - k * v1 - v2
+        } else {
+            v2
+        }
+    }
+}
+
+/// The effects applied to a single voice
+#[derive(Clone, Debug)]
+pub(crate) struct EffectChain {
+    /// Pre-gain of the overdrive, or None when off
+    drive: Option<f32>,
+    /// Quantization levels per polarity of the bit crusher, or None at 16 bits
+    levels: Option<f32>,
+    filter: Option<Filter>,
+}
+
+impl EffectChain {
+    pub(crate) fn new(step: &Step, sample_rate: u32) -> Self {
+        Self {
+            // overdrive is 0-10000
+            drive: (step.overdrive > 0).then(|| 1.0 + step.overdrive.min(10000) as f32 / 500.0),
+            // bit_depth is 4-16
+            levels: (step.bit_depth < 16)
+                .then(|| 2f32.powi(step.bit_depth.clamp(4, 16) as i32 - 1)),
+            filter: Filter::new(step.filter_cutoff, step.filter_resonance, sample_rate),
+        }
+    }
+
+    pub(crate) fn process(&mut self, channel: usize, x: f32) -> f32 {
+        let mut y = x;
+        if let Some(drive) = self.drive {
+            y = (y * drive).tanh() / drive.tanh();
+        }
+        if let Some(levels) = self.levels {
+            y = (y * levels).round() / levels;
+        }
+        if let Some(filter) = &mut self.filter {
+            y = filter.process(channel, y);
+        }
+        y
+    }
+}
@@ -0,0 +1,339 @@
+//! Offline rendering of patterns to audio, using the samples stored alongside a project.
+//!
+//! Chance and repeats are not rendered yet: every non-empty step plays exactly once. The order
+//! of the play modes that are [approximate](crate::PlayMode::is_approximate) is a guess.
+
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::units::volume_db;
+use crate::vfs::{self, Vfs};
+use crate::{ParseError, Pattern, PlayMode, Result, Step, Track, TrackSpeed};
+
+mod buses;
+mod effects;
+mod stems;
+mod wav;
+pub use buses::{DelayOptions, ReverbOptions};
+use effects::EffectChain;
+pub use stems::{StemOptions, Stems};
+pub use wav::{AudioBuffer, SampleFormat};
+
+/// The number of samples used to fade out a voice that is cut off by the next step on its track
+const DECLICK_FRAMES: usize = 64;
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct RenderOptions {
+    pub sample_rate: u32,
+    /// How many times the longest track is played
+    pub loops: usize,
+    /// Seconds rendered after the last loop, so that ringing samples and effects aren't cut off
+    pub tail: f32,
+    pub reverb: ReverbOptions,
+    pub delay: DelayOptions,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100,
+            loops: 1,
+            tail: 0.0,
+            reverb: ReverbOptions::default(),
+            delay: DelayOptions::default(),
+        }
+    }
+}
+
+/// The audio for each sample slot of a project
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct SamplePool {
+    slots: HashMap<u16, AudioBuffer>,
+}
+
+impl SamplePool {
+    /// Read every `[NNN] name.wav` file in a project's `samples` dir
+    #[cfg(feature = "fs")]
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::read_in(path, "")
+    }
+
+    /// Read the sample files of a project, wherever they are stored
+    pub fn from_files(files: &(impl Vfs + ?Sized)) -> Result<Self> {
+        Self::read_in(files, "samples")
+    }
+
+    fn read_in(files: &(impl Vfs + ?Sized), dir: &str) -> Result<Self> {
+        let mut pool = Self::default();
+        for name in vfs::list_dir(files, dir)? {
+            if let Some(slot) = sample_slot(&name) {
+                let path = vfs::join(dir, &name);
+                let bytes = vfs::read_file(files, &path)?;
+                pool.insert(
+                    slot,
+                    vfs::in_file(AudioBuffer::from_wav_bytes(&bytes), &path)?,
+                );
+            }
+        }
+        Ok(pool)
+    }
+
+    pub fn insert(&mut self, slot: u16, audio: AudioBuffer) {
+        self.slots.insert(slot, audio);
+    }
+
+    pub fn get(&self, slot: u16) -> Option<&AudioBuffer> {
+        self.slots.get(&slot)
+    }
+}
+
+/// The slot number of a sample file named `[NNN] name.wav`
+pub fn sample_slot(file_name: &str) -> Option<u16> {
+    static SAMPLE_FILE: OnceLock<Regex> = OnceLock::new();
+    let re = SAMPLE_FILE.get_or_init(|| Regex::new(r"^\[(\d+)\] .*\.wav$").unwrap());
+    re.captures(file_name)?.get(1)?.as_str().parse().ok()
+}
+
+/// Fails unless `bpm` is a positive number, which every step duration is divided by
+fn check_bpm(bpm: f32) -> Result<()> {
+    if bpm > 0.0 && bpm.is_finite() {
+        Ok(())
+    } else {
+        Err(ParseError(format!("Cannot render at {} bpm", bpm)))
+    }
+}
+
+/// The length of a step in seconds, or None if the track is paused
+fn step_duration(bpm: f32, track_speed: TrackSpeed) -> Option<f64> {
+    match track_speed {
+        TrackSpeed::Fraction(n, d) if n > 0 && d > 0 => {
+            // Steps are 16th notes at a speed of 1/1
+            Some(60.0 / bpm as f64 / 4.0 * d as f64 / n as f64)
+        }
+        _ => None,
+    }
+}
+
+/// When a step is triggered, in seconds from the start of the pattern
+struct Trigger<'a> {
+    time: f64,
+    step: &'a Step,
+}
+
+/// Every step `track` plays within `duration` seconds
+fn schedule(track: &Track<Step>, bpm: f32, duration: f64) -> Vec<Trigger<'_>> {
+    let step_len = match step_duration(bpm, track.track_speed) {
+        Some(l) if !track.steps.is_empty() => l,
+        _ => return vec![],
+    };
+    let mode = PlayMode::from(track.play_mode);
+    // Swing moves every second step; 50% is straight
+    let swing = (track.swing as f64 - 50.0) / 50.0 * step_len;
+
+    let mut triggers = vec![];
+    let mut position = 0;
+    while (position as f64) * step_len < duration {
+        let step = &track.steps[mode.step_at(position, track.steps.len())];
+        if !step.is_empty() {
+            let mut time = position as f64 * step_len;
+            if position % 2 == 1 {
+                time += swing;
+            }
+            // micro_move is in 24ths of a step
+            time += step.micro_move as f64 / 24.0 * step_len;
+            triggers.push(Trigger {
+                time: time.max(0.0),
+                step,
+            });
+        }
+        position += 1;
+    }
+    triggers
+}
+
+/// The length of a single loop of `pattern`: the duration of its longest, non-empty track
+pub fn loop_duration(pattern: &Pattern, bpm: f32) -> f64 {
+    (0..8)
+        .filter_map(|n| pattern.active_audio_track(n))
+        .filter(|t| t.steps.iter().any(|s| !s.is_empty()))
+        .filter_map(|t| step_duration(bpm, t.track_speed).map(|l| l * t.steps.len() as f64))
+        .fold(0.0, f64::max)
+}
+
+/// Equal-power pan gains, normalized so that the center is at unity gain on both sides
+fn pan_gains(pan: i16) -> (f32, f32) {
+    let angle = (pan as f32 / 10000.0 + 1.0) * FRAC_PI_4;
+    (angle.cos() * SQRT_2, angle.sin() * SQRT_2)
+}
+
+/// Linear gain of a step's volume
+fn volume_gain(volume: u16) -> f32 {
+    volume_db(volume)
+        .map(|db| 10f32.powf(db / 20.0))
+        .unwrap_or(0.0)
+}
+
+/// Render a single step, up to at most `max_frames` frames
+fn render_voice(
+    step: &Step,
+    sample: &AudioBuffer,
+    sample_rate: u32,
+    max_frames: usize,
+) -> AudioBuffer {
+    let len = sample.frames();
+    if len == 0 {
+        return AudioBuffer::new(sample_rate, 2, 0);
+    }
+    // 0 is the first frame of the sample and 32767 the last
+    let start = step.sample_start.max(0) as f64 / 32767.0 * (len - 1) as f64;
+    let end = step.sample_end.max(0) as f64 / 32767.0 * (len - 1) as f64;
+    let direction = if start > end { -1.0 } else { 1.0 };
+
+    // C4 (60) plays the sample at its original pitch; micro_tune is ±100 cents
+    let semitones = step.note as f64 - 60.0 + step.micro_tune as f64 / 10000.0;
+    let rate = 2f64.powf(semitones / 12.0) * sample.sample_rate as f64 / sample_rate as f64;
+
+    let region_frames = ((end - start).abs() / rate) as usize;
+    let frames = region_frames.min(max_frames);
+    // Attack and decay are a proportion of the played region
+    let attack = step.sample_attack as f32 / 10000.0 * region_frames as f32;
+    let decay = step.sample_decay as f32 / 10000.0 * region_frames as f32;
+
+    let gain = volume_gain(step.volume);
+    let (left_gain, right_gain) = pan_gains(step.pan);
+    let mut effects = EffectChain::new(step, sample_rate);
+
+    let mut voice = AudioBuffer::new(sample_rate, 2, frames);
+    for i in 0..frames {
+        let pos = start + direction * i as f64 * rate;
+        let index = pos.floor() as usize;
+        if pos < 0.0 || index >= len {
+            break;
+        }
+        let frac = (pos - index as f64) as f32;
+        let (l0, r0) = sample.frame(index);
+        let (l1, r1) = sample.frame((index + 1).min(len - 1));
+
+        let mut envelope = 1.0;
+        if (i as f32) < attack {
+            envelope *= i as f32 / attack;
+        }
+        let remaining = (region_frames - i) as f32;
+        if remaining < decay {
+            envelope *= remaining / decay;
+        }
+        if frames < region_frames && frames - i < DECLICK_FRAMES {
+            envelope *= (frames - i) as f32 / DECLICK_FRAMES as f32;
+        }
+
+        let l = effects.process(0, l0 + (l1 - l0) * frac);
+        let r = effects.process(1, r0 + (r1 - r0) * frac);
+        let g = gain * envelope;
+        voice.channels[0][i] = l * g * left_gain;
+        voice.channels[1][i] = r * g * right_gain;
+    }
+    voice
+}
+
+/// The dry signal of a track along with what it sends to the reverb and delay buses
+struct TrackRender {
+    dry: AudioBuffer,
+    reverb_send: AudioBuffer,
+    delay_send: AudioBuffer,
+}
+
+impl Pattern {
+    /// The length of a render of the pattern, including the tail
+    fn render_frames(&self, bpm: f32, options: &RenderOptions) -> usize {
+        let duration = loop_duration(self, bpm) * options.loops as f64;
+        ((duration + options.tail as f64) * options.sample_rate as f64).ceil() as usize
+    }
+
+    fn render_track_sends(
+        &self,
+        track: usize,
+        bpm: f32,
+        samples: &SamplePool,
+        options: &RenderOptions,
+    ) -> TrackRender {
+        let duration = loop_duration(self, bpm) * options.loops as f64;
+        let total_frames = self.render_frames(bpm, options);
+        let mut out = TrackRender {
+            dry: AudioBuffer::new(options.sample_rate, 2, total_frames),
+            reverb_send: AudioBuffer::new(options.sample_rate, 2, total_frames),
+            delay_send: AudioBuffer::new(options.sample_rate, 2, total_frames),
+        };
+
+        let track = match self.active_audio_track(track) {
+            Some(t) => t,
+            None => return out,
+        };
+        let triggers = schedule(track, bpm, duration);
+        for (i, trigger) in triggers.iter().enumerate() {
+            let sample = match samples.get(trigger.step.sample) {
+                Some(s) => s,
+                None => continue,
+            };
+            let offset = (trigger.time * options.sample_rate as f64).round() as usize;
+            // Tracks are monophonic: the next step cuts this one off
+            let max_frames = triggers
+                .get(i + 1)
+                .map(|next| (next.time * options.sample_rate as f64).round() as usize)
+                .unwrap_or(total_frames)
+                .saturating_sub(offset);
+            let voice = render_voice(trigger.step, sample, options.sample_rate, max_frames);
+            // Sends are post-fader; 10000 is 100%
+            out.reverb_send.mix_with_gain(
+                &voice,
+                offset,
+                trigger.step.reverb.max(0) as f32 / 10000.0,
+            );
+            out.delay_send.mix_with_gain(
+                &voice,
+                offset,
+                trigger.step.delay.max(0) as f32 / 10000.0,
+            );
+            out.dry.mix(&voice, offset);
+        }
+        out
+    }
+
+    /// Render a single audio track (the active variation), for `options.loops` loops of the
+    /// pattern. Only the dry signal is included: sends to the reverb and delay are ignored.
+    ///
+    /// Fails if `bpm` isn't positive.
+    pub fn render_track(
+        &self,
+        track: usize,
+        bpm: f32,
+        samples: &SamplePool,
+        options: &RenderOptions,
+    ) -> Result<AudioBuffer> {
+        check_bpm(bpm)?;
+        Ok(self.render_track_sends(track, bpm, samples, options).dry)
+    }
+
+    /// Render every audio track of the pattern along with the reverb and delay returns, mixed
+    /// to stereo. Fails if `bpm` isn't positive.
+    pub fn render(
+        &self,
+        bpm: f32,
+        samples: &SamplePool,
+        options: &RenderOptions,
+    ) -> Result<AudioBuffer> {
+        let stems = self.render_stems(
+            bpm,
+            samples,
+            &StemOptions {
+                render: options.clone(),
+                ..Default::default()
+            },
+        )?;
+        Ok(stems.mix())
+    }
+}
@@ -0,0 +1,185 @@
+//! Rendering each audio track to its own file, for mixing elsewhere.
+
+#[cfg(feature = "fs")]
+use std::io;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+use super::{buses, check_bpm, AudioBuffer, RenderOptions, SampleFormat, SamplePool, TrackRender};
+use crate::{Pattern, Project, Result};
+
+/// How to render stems, and which tracks to render.
+///
+/// Projects don't store which tracks were muted or soloed: the device clears both when a
+/// project is loaded. So by default no track is muted or soloed, as on the device.
+#[derive(PartialEq, Clone, Debug)]
+pub struct StemOptions {
+    pub render: RenderOptions,
+    pub format: SampleFormat,
+    /// Whether to render the reverb and delay returns as their own stems
+    pub returns: bool,
+    /// Tracks that are not rendered, and don't send anything to the reverb or delay
+    pub muted: [bool; 8],
+    /// If any tracks are soloed, only those are rendered
+    pub soloed: [bool; 8],
+}
+
+impl Default for StemOptions {
+    fn default() -> Self {
+        Self {
+            render: RenderOptions::default(),
+            format: SampleFormat::default(),
+            returns: true,
+            muted: [false; 8],
+            soloed: [false; 8],
+        }
+    }
+}
+
+impl StemOptions {
+    /// Whether `track` should be rendered, given the mute and solo selection
+    pub fn is_audible(&self, track: usize) -> bool {
+        if self.soloed.iter().any(|&s| s) {
+            self.soloed[track]
+        } else {
+            !self.muted[track]
+        }
+    }
+}
+
+/// Audio for each track of a pattern. Every stem has the same length.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Stems {
+    /// The dry signal of each audible track, by track number
+    pub tracks: Vec<(usize, AudioBuffer)>,
+    pub reverb: Option<AudioBuffer>,
+    pub delay: Option<AudioBuffer>,
+    pub sample_rate: u32,
+    /// The length of every stem, which is that of the song even when no track is audible
+    pub frames: usize,
+}
+
+impl Stems {
+    /// All of the stems summed together, in stereo. Silent if no track is audible.
+    pub fn mix(&self) -> AudioBuffer {
+        let all = self
+            .tracks
+            .iter()
+            .map(|(_, audio)| audio)
+            .chain(&self.reverb)
+            .chain(&self.delay);
+        let mut out = AudioBuffer::new(self.sample_rate, 2, self.frames);
+        for audio in all {
+            out.mix(audio, 0);
+        }
+        out
+    }
+
+    /// Write each stem to `dir` as `track N.wav` (numbered from 1, like on the device),
+    /// `reverb.wav` and `delay.wav`. Returns the paths that were written.
+    #[cfg(feature = "fs")]
+    pub fn write(&self, dir: &Path, format: SampleFormat) -> io::Result<Vec<PathBuf>> {
+        let tracks = self
+            .tracks
+            .iter()
+            .map(|(n, audio)| (format!("track {}.wav", n + 1), audio));
+        let returns = [("reverb.wav", &self.reverb), ("delay.wav", &self.delay)]
+            .into_iter()
+            .filter_map(|(name, audio)| audio.as_ref().map(|a| (name.to_string(), a)));
+
+        let mut paths = vec![];
+        for (name, audio) in tracks.chain(returns) {
+            let path = dir.join(name);
+            audio.write_wav(&path, format)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+impl Pattern {
+    /// Render each audible audio track separately, along with the reverb and delay returns.
+    ///
+    /// When `options.returns` is false, the returns are not rendered at all, rather than being
+    /// mixed into the track stems. Fails if `bpm` isn't positive.
+    pub fn render_stems(
+        &self,
+        bpm: f32,
+        samples: &SamplePool,
+        options: &StemOptions,
+    ) -> Result<Stems> {
+        check_bpm(bpm)?;
+        let render = &options.render;
+        let mut tracks = vec![];
+        let mut reverb_send: Option<AudioBuffer> = None;
+        let mut delay_send: Option<AudioBuffer> = None;
+        for track in (0..8).filter(|&t| options.is_audible(t)) {
+            let TrackRender {
+                dry,
+                reverb_send: reverb,
+                delay_send: delay,
+            } = self.render_track_sends(track, bpm, samples, render);
+            for (bus, send) in [(&mut reverb_send, reverb), (&mut delay_send, delay)] {
+                match bus {
+                    Some(b) => b.mix(&send, 0),
+                    None => *bus = Some(send),
+                }
+            }
+            tracks.push((track, dry));
+        }
+
+        let (reverb, delay) = if options.returns {
+            (
+                reverb_send.map(|send| buses::reverb(&send, &render.reverb)),
+                delay_send.map(|send| buses::delay(&send, bpm, &render.delay)),
+            )
+        } else {
+            (None, None)
+        };
+
+        Ok(Stems {
+            tracks,
+            reverb,
+            delay,
+            sample_rate: render.sample_rate,
+            frames: self.render_frames(bpm, render),
+        })
+    }
+}
+
+impl Project {
+    /// Render the stems of pattern number `pattern`, using this project's bpm. None if there
+    /// is no such pattern; fails if the bpm isn't positive.
+    pub fn render_stems(
+        &self,
+        pattern: u8,
+        samples: &SamplePool,
+        options: &StemOptions,
+    ) -> Result<Option<Stems>> {
+        self.pattern(pattern)
+            .map(|p| p.render_stems(self.settings.bpm, samples, options))
+            .transpose()
+    }
+
+    /// Render the stems of pattern number `pattern` into `dir`. Returns the paths that were
+    /// written.
+    #[cfg(feature = "fs")]
+    pub fn write_stems(
+        &self,
+        pattern: u8,
+        samples: &SamplePool,
+        dir: &Path,
+        options: &StemOptions,
+    ) -> io::Result<Vec<PathBuf>> {
+        let stems = self
+            .render_stems(pattern, samples, options)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.0))?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No pattern number {}", pattern),
+                )
+            })?;
+        stems.write(dir, options.format)
+    }
+}
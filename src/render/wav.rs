@@ -0,0 +1,212 @@
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::io::{BufWriter, Read};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+
+use crate::{ParseError, Result};
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Sample formats that can be written to a WAV file
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum SampleFormat {
+    Int16,
+    #[default]
+    Int24,
+    Float32,
+}
+
+impl SampleFormat {
+    fn bits(self) -> u16 {
+        match self {
+            SampleFormat::Int16 => 16,
+            SampleFormat::Int24 => 24,
+            SampleFormat::Float32 => 32,
+        }
+    }
+}
+
+/// Non-interleaved audio, with samples in the range -1.0..1.0
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct AudioBuffer {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl AudioBuffer {
+    /// A silent buffer
+    pub fn new(sample_rate: u32, num_channels: usize, frames: usize) -> Self {
+        Self {
+            sample_rate,
+            channels: vec![vec![0.0; frames]; num_channels],
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.channels.first().map(|c| c.len()).unwrap_or(0)
+    }
+
+    /// The (left, right) value of a frame. Mono buffers are played on both sides.
+    pub fn frame(&self, n: usize) -> (f32, f32) {
+        match self.channels.len() {
+            0 => (0.0, 0.0),
+            1 => (self.channels[0][n], self.channels[0][n]),
+            _ => (self.channels[0][n], self.channels[1][n]),
+        }
+    }
+
+    /// The largest absolute sample value
+    pub fn peak(&self) -> f32 {
+        self.channels
+            .iter()
+            .flatten()
+            .fold(0.0, |peak: f32, s| peak.max(s.abs()))
+    }
+
+    /// Add `other` into this buffer, starting at frame `offset`. Anything that doesn't fit is dropped.
+    pub fn mix(&mut self, other: &AudioBuffer, offset: usize) {
+        self.mix_with_gain(other, offset, 1.0)
+    }
+
+    /// Like [AudioBuffer::mix], with `other` scaled by `gain`
+    pub fn mix_with_gain(&mut self, other: &AudioBuffer, offset: usize, gain: f32) {
+        if gain == 0.0 || other.channels.is_empty() {
+            return;
+        }
+        for (c, channel) in self.channels.iter_mut().enumerate() {
+            let src = &other.channels[c.min(other.channels.len() - 1)];
+            for (dst, s) in channel.iter_mut().skip(offset).zip(src) {
+                *dst += s * gain;
+            }
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn read_wav(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)
+            .map_err(|_| ParseError(format!("Cannot read wav file: {:?}", &path)))?;
+
+        let mut buf: Vec<u8> = vec![];
+        file.read_to_end(&mut buf)
+            .map_err(|e| ParseError(format!("Cannot read wav file {:?}: {}", &path, e)))?;
+        Self::from_wav_bytes(&buf).map_err(|e| ParseError(format!("{:?}: {}", &path, e.0)))
+    }
+
+    pub fn from_wav_bytes(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 12 || &buf[0..4] != b"RIFF" || &buf[8..12] != b"WAVE" {
+            return Err(ParseError("Not a RIFF/WAVE file".to_string()));
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut pos = 12;
+        while pos + 8 <= buf.len() {
+            let id = &buf[pos..pos + 4];
+            let len = LittleEndian::read_u32(&buf[pos + 4..pos + 8]) as usize;
+            let body = &buf[pos + 8..(pos + 8 + len).min(buf.len())];
+            match id {
+                b"fmt " => {
+                    if body.len() < 16 {
+                        return Err(ParseError("Truncated fmt chunk".to_string()));
+                    }
+                    let mut tag = LittleEndian::read_u16(&body[0..2]);
+                    if tag == FORMAT_EXTENSIBLE && body.len() >= 26 {
+                        // The first two bytes of the sub-format GUID are the format tag
+                        tag = LittleEndian::read_u16(&body[24..26]);
+                    }
+                    let channels = LittleEndian::read_u16(&body[2..4]);
+                    let sample_rate = LittleEndian::read_u32(&body[4..8]);
+                    let bits = LittleEndian::read_u16(&body[14..16]);
+                    format = Some((tag, channels as usize, sample_rate, bits));
+                }
+                b"data" => data = Some(body),
+                _ => (),
+            }
+            // Chunks are padded to an even length
+            pos += 8 + len + (len & 1);
+        }
+
+        let (tag, num_channels, sample_rate, bits) =
+            format.ok_or_else(|| ParseError("Missing fmt chunk".to_string()))?;
+        let data = data.ok_or_else(|| ParseError("Missing data chunk".to_string()))?;
+        if num_channels == 0 {
+            return Err(ParseError("WAV file has no channels".to_string()));
+        }
+
+        let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+            (FORMAT_PCM, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+            (FORMAT_PCM, 16) => |b| LittleEndian::read_i16(b) as f32 / 32768.0,
+            (FORMAT_PCM, 24) => |b| LittleEndian::read_i24(b) as f32 / 8388608.0,
+            (FORMAT_PCM, 32) => |b| LittleEndian::read_i32(b) as f32 / 2147483648.0,
+            (FORMAT_FLOAT, 32) => LittleEndian::read_f32,
+            (FORMAT_FLOAT, 64) => |b| LittleEndian::read_f64(b) as f32,
+            _ => {
+                return Err(ParseError(format!(
+                    "Unsupported WAV format {} with {} bits per sample",
+                    tag, bits
+                )))
+            }
+        };
+
+        let width = bits as usize / 8;
+        let frames = data.len() / (width * num_channels);
+        let mut audio = Self::new(sample_rate, num_channels, frames);
+        for (i, frame) in data.chunks_exact(width * num_channels).enumerate() {
+            for (c, s) in frame.chunks_exact(width).enumerate() {
+                audio.channels[c][i] = decode(s);
+            }
+        }
+
+        Ok(audio)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn write_wav(&self, path: &Path, format: SampleFormat) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write_wav_to(&mut file, format)?;
+        file.flush()
+    }
+
+    pub fn write_wav_to<W: Write>(&self, w: &mut W, format: SampleFormat) -> std::io::Result<()> {
+        let num_channels = self.channels.len() as u16;
+        let block_align = num_channels * format.bits() / 8;
+        let data_len = self.frames() as u32 * block_align as u32;
+
+        w.write_all(b"RIFF")?;
+        w.write_u32::<LittleEndian>(36 + data_len)?;
+        w.write_all(b"WAVE")?;
+
+        w.write_all(b"fmt ")?;
+        w.write_u32::<LittleEndian>(16)?;
+        w.write_u16::<LittleEndian>(match format {
+            SampleFormat::Float32 => FORMAT_FLOAT,
+            _ => FORMAT_PCM,
+        })?;
+        w.write_u16::<LittleEndian>(num_channels)?;
+        w.write_u32::<LittleEndian>(self.sample_rate)?;
+        w.write_u32::<LittleEndian>(self.sample_rate * block_align as u32)?;
+        w.write_u16::<LittleEndian>(block_align)?;
+        w.write_u16::<LittleEndian>(format.bits())?;
+
+        w.write_all(b"data")?;
+        w.write_u32::<LittleEndian>(data_len)?;
+        for i in 0..self.frames() {
+            for channel in &self.channels {
+                let s = channel[i].clamp(-1.0, 1.0);
+                match format {
+                    SampleFormat::Int16 => w.write_i16::<LittleEndian>((s * 32767.0) as i16)?,
+                    SampleFormat::Int24 => w.write_i24::<LittleEndian>((s * 8388607.0) as i32)?,
+                    SampleFormat::Float32 => w.write_f32::<LittleEndian>(s)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
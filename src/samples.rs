@@ -0,0 +1,310 @@
+//! The sample files of a project, and adding, replacing, moving, removing and pruning its
+//! samples.
+
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "fs")]
+use std::fs;
+use std::io;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::render::{sample_slot, AudioBuffer};
+use crate::{ParseError, Project, Result, Samples, Step, TrackStep, Vfs};
+
+/// A sample audio file of a project, stored in its `samples` directory as `[NNN] name.wav`
+#[derive(PartialEq, Clone, Debug)]
+pub struct SampleFile {
+    /// The sample slot, from the number in the file name
+    pub slot: u16,
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+}
+
+impl SampleFile {
+    /// A sample file for `slot`, named after `name`, which may end in `.wav`
+    pub fn new(slot: u16, name: &str, bytes: Vec<u8>) -> Self {
+        Self {
+            slot,
+            file_name: file_name(slot, name),
+            bytes,
+        }
+    }
+
+    /// The name of the sample, without its slot number and extension
+    pub fn name(&self) -> &str {
+        let name = self
+            .file_name
+            .strip_suffix(".wav")
+            .unwrap_or(&self.file_name);
+        name.split_once("] ").map_or(name, |(_, name)| name)
+    }
+
+    /// Read the sample files of the project in the directory `path`
+    #[cfg(feature = "fs")]
+    pub fn read_dir(path: &Path) -> io::Result<Vec<Self>> {
+        Self::from_files(path)
+    }
+
+    /// Read the sample files of a project, wherever they are stored
+    pub fn from_files(files: &(impl Vfs + ?Sized)) -> io::Result<Vec<Self>> {
+        let mut samples = vec![];
+        for file_name in files.list_dir("samples")? {
+            let Some(slot) = sample_slot(&file_name) else {
+                continue;
+            };
+            if let Some(bytes) = files.read_file(&format!("samples/{}", file_name))? {
+                samples.push(Self {
+                    slot,
+                    file_name,
+                    bytes,
+                });
+            }
+        }
+        samples.sort_by_key(|s| s.slot);
+        Ok(samples)
+    }
+
+    /// Write the sample files to the `samples` directory of the project in the directory
+    /// `path`, and remove the sample files that are not among them
+    #[cfg(feature = "fs")]
+    pub fn write_dir(path: &Path, samples: &[Self]) -> io::Result<()> {
+        let dir = path.join("samples");
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            let file_name = entry?.file_name();
+            let Some(name) = file_name.to_str() else {
+                continue;
+            };
+            if sample_slot(name).is_some() && !samples.iter().any(|s| s.file_name == name) {
+                fs::remove_file(dir.join(name))?;
+            }
+        }
+        for sample in samples {
+            fs::write(dir.join(&sample.file_name), &sample.bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// What [`Project::prune_samples`] removed
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct PruneReport {
+    /// The slots of the sample files that no note used
+    pub unused: Vec<u16>,
+    /// The slot of each sample file that was the same as another, and the slot of that file,
+    /// before the slots were compacted
+    pub duplicates: Vec<(u16, u16)>,
+    /// The size of the files that were removed
+    pub bytes_saved: usize,
+}
+
+impl Samples {
+    /// Move the folder of slot `from` to slot `to`, shifting the slots in between.
+    ///
+    /// Panics if either slot is beyond `slot_folders`.
+    pub fn move_slot(&mut self, from: u16, to: u16) {
+        let folder = self.slot_folders.remove(from as usize);
+        self.slot_folders.insert(to as usize, folder);
+    }
+
+    /// Put slot `slot` back in the first folder.
+    ///
+    /// Panics if the slot is beyond `slot_folders`.
+    pub fn clear_slot(&mut self, slot: u16) {
+        self.slot_folders[slot as usize] = 0;
+    }
+
+    fn check_slot(&self, slot: u16) -> Result<()> {
+        if slot as usize >= self.slot_folders.len() {
+            return Err(ParseError(format!("No sample slot {}", slot)));
+        }
+        Ok(())
+    }
+}
+
+impl Project {
+    /// Add a WAV file to the first slot that has no file and is not used by any note.
+    ///
+    /// `files` are the sample files of the project, as read by [`SampleFile::read_dir`].
+    /// Returns the slot of the sample.
+    pub fn add_sample(
+        &self,
+        files: &mut Vec<SampleFile>,
+        name: &str,
+        bytes: Vec<u8>,
+    ) -> Result<u16> {
+        check_wav(&bytes)?;
+        let used = self.used_samples();
+        let slot = (0..self.samples.slot_folders.len() as u16)
+            .find(|&slot| !used.contains(&slot) && !files.iter().any(|f| f.slot == slot))
+            .ok_or_else(|| ParseError("Every sample slot is used".to_string()))?;
+        files.push(SampleFile::new(slot, name, bytes));
+        files.sort_by_key(|f| f.slot);
+        Ok(slot)
+    }
+
+    /// Replace the audio of the sample in `slot`, keeping its name
+    pub fn replace_sample(
+        &self,
+        files: &mut [SampleFile],
+        slot: u16,
+        bytes: Vec<u8>,
+    ) -> Result<()> {
+        check_wav(&bytes)?;
+        let file = files
+            .iter_mut()
+            .find(|f| f.slot == slot)
+            .ok_or_else(|| ParseError(format!("No sample file in slot {}", slot)))?;
+        file.bytes = bytes;
+        Ok(())
+    }
+
+    /// Move the sample in slot `from` to slot `to`, shifting the samples in between by one
+    /// slot, as when reordering a list. The notes of every pattern and variation are changed
+    /// to keep playing the same samples.
+    pub fn move_sample(&mut self, files: &mut [SampleFile], from: u16, to: u16) -> Result<()> {
+        self.samples.check_slot(from)?;
+        self.samples.check_slot(to)?;
+        let moved = |slot: u16| {
+            if slot == from {
+                to
+            } else if from < to && (from + 1..=to).contains(&slot) {
+                slot - 1
+            } else if to < from && (to..from).contains(&slot) {
+                slot + 1
+            } else {
+                slot
+            }
+        };
+        self.samples.move_slot(from, to);
+        self.remap_samples(|slot| Some(moved(slot)));
+        for file in files.iter_mut() {
+            file.slot = moved(file.slot);
+            file.file_name = file_name(file.slot, file.name());
+        }
+        files.sort_by_key(|f| f.slot);
+        Ok(())
+    }
+
+    /// Remove the sample in `slot`. Its file is removed from `files`, and the notes that play
+    /// it are cleared, leaving the other slots as they are.
+    ///
+    /// Returns the number of notes that were cleared.
+    pub fn remove_sample(&mut self, files: &mut Vec<SampleFile>, slot: u16) -> Result<usize> {
+        self.samples.check_slot(slot)?;
+        self.samples.clear_slot(slot);
+        files.retain(|f| f.slot != slot);
+        Ok(self.remap_samples(|s| (s != slot).then_some(s)))
+    }
+
+    /// Remove the sample files that no note uses, and merge the files with the same bytes or
+    /// the same audio into the one with the lowest slot. The remaining samples are then moved
+    /// to the first slots, keeping their order, and the notes of every pattern and variation
+    /// are changed to keep playing the same samples.
+    pub fn prune_samples(&mut self, files: &mut Vec<SampleFile>) -> PruneReport {
+        let used = self.used_samples();
+        let mut report = PruneReport::default();
+        let (kept, unused): (Vec<_>, Vec<_>) =
+            files.drain(..).partition(|f| used.contains(&f.slot));
+        for file in unused {
+            report.unused.push(file.slot);
+            report.bytes_saved += file.bytes.len();
+        }
+
+        // Files that can't be decoded are only compared by their bytes
+        let audio: Vec<_> = kept
+            .iter()
+            .map(|f| AudioBuffer::from_wav_bytes(&f.bytes).ok())
+            .collect();
+        let mut same = BTreeMap::new();
+        let mut unique: Vec<usize> = vec![];
+        for (i, file) in kept.iter().enumerate() {
+            let original = unique.iter().copied().find(|&j| {
+                kept[j].bytes == file.bytes || (audio[i].is_some() && audio[i] == audio[j])
+            });
+            match original {
+                Some(j) => {
+                    report.duplicates.push((file.slot, kept[j].slot));
+                    report.bytes_saved += file.bytes.len();
+                    same.insert(file.slot, kept[j].slot);
+                }
+                None => unique.push(i),
+            }
+        }
+        files.extend(unique.into_iter().map(|i| kept[i].clone()));
+
+        // Notes of slots without a file keep a slot of their own
+        let slots: BTreeSet<u16> = used
+            .iter()
+            .map(|s| *same.get(s).unwrap_or(s))
+            .chain(files.iter().map(|f| f.slot))
+            .collect();
+        let compacted: BTreeMap<u16, u16> = slots
+            .into_iter()
+            .enumerate()
+            .map(|(i, slot)| (slot, i as u16))
+            .collect();
+        let mut slot_folders = vec![0; self.samples.slot_folders.len()];
+        for (&from, &to) in &compacted {
+            if let (Some(folder), Some(&from_folder)) = (
+                slot_folders.get_mut(to as usize),
+                self.samples.slot_folders.get(from as usize),
+            ) {
+                *folder = from_folder;
+            }
+        }
+        self.samples.slot_folders = slot_folders;
+        self.remap_samples(|slot| Some(compacted[same.get(&slot).unwrap_or(&slot)]));
+        for file in files.iter_mut() {
+            file.slot = compacted[&file.slot];
+            file.file_name = file_name(file.slot, file.name());
+        }
+        files.sort_by_key(|f| f.slot);
+        report
+    }
+
+    /// The slots that are used by a note of any pattern or variation
+    pub fn used_samples(&self) -> BTreeSet<u16> {
+        self.patterns
+            .iter()
+            .flat_map(|p| p.audio_tracks.iter().flatten().flatten())
+            .flat_map(|t| &t.steps)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.sample)
+            .collect()
+    }
+
+    /// Change the sample of every note to `f(sample)`, or clear the note when that is None.
+    /// Returns the number of notes that were cleared.
+    pub(crate) fn remap_samples(&mut self, f: impl Fn(u16) -> Option<u16>) -> usize {
+        let mut cleared = 0;
+        let tracks = self
+            .patterns
+            .iter_mut()
+            .flat_map(|p| p.audio_tracks.iter_mut().flatten().flatten());
+        for step in tracks.flat_map(|t| &mut t.steps).filter(|s| !s.is_empty()) {
+            match f(step.sample) {
+                Some(sample) => step.sample = sample,
+                None => {
+                    let mut empty = Step::default();
+                    empty.set_number(step.number);
+                    *step = empty;
+                    cleared += 1;
+                }
+            }
+        }
+        cleared
+    }
+}
+
+fn file_name(slot: u16, name: &str) -> String {
+    let name = name.strip_suffix(".wav").unwrap_or(name).replace('/', "_");
+    format!("[{:03}] {}.wav", slot, name)
+}
+
+fn check_wav(bytes: &[u8]) -> Result<()> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(ParseError("Not a RIFF/WAVE file".to_string()));
+    }
+    Ok(())
+}
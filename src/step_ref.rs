@@ -0,0 +1,232 @@
+//! Borrowed views of the audio steps in pattern and track files, for reading a few values of
+//! many steps without parsing whole tracks.
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::reader::Reader;
+use crate::{ParseError, Result, Step};
+
+/// An audio step in the bytes of a pattern or track file. Its values are decoded when they are
+/// used.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct StepRef<'a> {
+    number: usize,
+    values: &'a [u8],
+    rest: &'a [u8],
+}
+
+/// The number of bytes of the values of a step
+const VALUES_LEN: usize = 42;
+
+/// Read the header of a step, in a pattern or track file. Returns the bytes of its values and
+/// the bytes after them.
+pub(crate) fn read_step<'a>(
+    reader: &mut Reader<'a>,
+    number: usize,
+) -> Result<(&'a [u8], &'a [u8])> {
+    reader.read_tag(0x0A, format_args!("step {}", number))?; // first byte tag (0x0A)
+    let len = reader.read_variable_quantity()?; // Length of step data
+
+    let start_pos = reader.pos();
+    reader.read_tag(0x0A, format_args!("step {}", number))?; // Second tag (0x0A)
+    let num_elements = reader.read_variable_quantity()?; // Length of step data
+    if num_elements != 44 {
+        // I've never seen a value that's not 44
+        return Err(ParseError(format!(
+            "Expected 44 values in step {}, found {}",
+            number, num_elements
+        )));
+    }
+
+    let values = reader.read_bytes(VALUES_LEN)?;
+    let bytes_advanced = reader.pos() - start_pos;
+    // Rest appears to be two empty bytes for empty notes and 8 bytes otherwise
+    let rest_len = len.checked_sub(bytes_advanced).ok_or_else(|| {
+        ParseError(format!(
+            "Step {} is shorter than its values: {} bytes",
+            number, len
+        ))
+    })?;
+    let rest = reader.read_bytes(rest_len)?; // Unknown data
+    Ok((values, rest))
+}
+
+impl<'a> StepRef<'a> {
+    pub(crate) fn from_reader(reader: &mut Reader<'a>, number: usize) -> Result<Self> {
+        let (values, rest) = read_step(reader, number)?;
+        Ok(Self {
+            number,
+            values,
+            rest,
+        })
+    }
+
+    fn steps(reader: &mut Reader<'a>) -> Result<Vec<Self>> {
+        let mut steps = Vec::with_capacity(64);
+        for step in 0..64 {
+            steps.push(Self::from_reader(reader, step)?);
+        }
+        Ok(steps)
+    }
+
+    /// The 64 steps stored in the track file of an audio track, including those past the end
+    /// of the track. Fails if the bytes don't hold 64 steps.
+    pub fn track_file(bytes: &'a [u8]) -> Result<Vec<Self>> {
+        Self::steps(&mut Reader::new(bytes))
+    }
+
+    /// The 64 steps stored in a pattern file for the active variation of the audio track
+    /// `track`, including those past the end of the track. Fails if the bytes don't hold 64
+    /// steps for the track.
+    pub fn pattern_file(bytes: &'a [u8], track: usize) -> Result<Vec<Self>> {
+        if track >= 8 {
+            return Err(ParseError(format!("No audio track {}", track)));
+        }
+        let mut reader = Reader::new(bytes);
+        for n in 0..track {
+            reader.read_tag(0x0A, format_args!("track {}", n))?; // Track tag (0x0A)
+            let len = reader.read_variable_quantity()?;
+            reader.read_bytes(len)?;
+        }
+        reader.read_tag(0x0A, format_args!("track {}", track))?; // Track tag (0x0A)
+        reader.read_variable_quantity()?;
+        Self::steps(&mut reader)
+    }
+
+    fn u16(&self, offset: usize) -> u16 {
+        LittleEndian::read_u16(&self.values[offset..])
+    }
+
+    fn i16(&self, offset: usize) -> i16 {
+        LittleEndian::read_i16(&self.values[offset..])
+    }
+
+    /// Step number, 0 indexed
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Whether there is a note on this step
+    pub fn is_empty(&self) -> bool {
+        // Steps with a note have a trailing bitmask of the values that are set
+        self.rest.len() <= 2
+    }
+
+    pub fn volume(&self) -> u16 {
+        self.u16(0)
+    }
+
+    pub fn pan(&self) -> i16 {
+        self.i16(2)
+    }
+
+    pub fn filter_cutoff(&self) -> i16 {
+        self.i16(4)
+    }
+
+    pub fn filter_resonance(&self) -> u16 {
+        self.u16(6)
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        self.u16(8) as u8
+    }
+
+    pub fn overdrive(&self) -> u16 {
+        self.u16(10)
+    }
+
+    pub fn note(&self) -> u8 {
+        self.u16(12) as u8
+    }
+
+    pub fn delay(&self) -> i16 {
+        self.i16(14)
+    }
+
+    pub fn reverb(&self) -> i16 {
+        self.i16(16)
+    }
+
+    pub fn sample(&self) -> u16 {
+        self.u16(18)
+    }
+
+    pub fn sample_start(&self) -> i16 {
+        self.i16(20)
+    }
+
+    pub fn sample_end(&self) -> i16 {
+        self.i16(22)
+    }
+
+    pub fn micro_tune(&self) -> i16 {
+        self.i16(24)
+    }
+
+    pub fn sample_attack(&self) -> u16 {
+        self.u16(26)
+    }
+
+    pub fn sample_decay(&self) -> u16 {
+        self.u16(28)
+    }
+
+    pub fn sample_folder(&self) -> u16 {
+        self.u16(30)
+    }
+
+    pub fn repeat_type(&self) -> u16 {
+        self.u16(32)
+    }
+
+    pub fn repeat_grid(&self) -> u16 {
+        self.u16(34)
+    }
+
+    pub fn chance_type(&self) -> u16 {
+        self.u16(36)
+    }
+
+    pub fn chance_action(&self) -> u16 {
+        self.u16(38)
+    }
+
+    pub fn micro_move(&self) -> i16 {
+        self.i16(40)
+    }
+
+    /// The bytes after the values of the step
+    pub fn rest(&self) -> &'a [u8] {
+        self.rest
+    }
+
+    /// Decode all of the values of the step
+    pub fn to_step(&self) -> Step {
+        Step {
+            number: self.number,
+            sample: self.sample(),
+            note: self.note(),
+            volume: self.volume(),
+            pan: self.pan(),
+            filter_cutoff: self.filter_cutoff(),
+            filter_resonance: self.filter_resonance(),
+            micro_move: self.micro_move(),
+            micro_tune: self.micro_tune(),
+            sample_start: self.sample_start(),
+            sample_end: self.sample_end(),
+            sample_attack: self.sample_attack(),
+            sample_decay: self.sample_decay(),
+            sample_folder: self.sample_folder(),
+            repeat_type: self.repeat_type(),
+            repeat_grid: self.repeat_grid(),
+            chance_type: self.chance_type(),
+            chance_action: self.chance_action(),
+            reverb: self.reverb(),
+            delay: self.delay(),
+            overdrive: self.overdrive(),
+            bit_depth: self.bit_depth(),
+            rest: self.rest.to_vec(),
+        }
+    }
+}
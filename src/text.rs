@@ -0,0 +1,591 @@
+//! A compact, tracker-style text notation for steps: one line per step, such as
+//! `03 C#4 S002 V+2.0dB P3R REV4 DLY5`.
+//!
+//! A line starts with the step number and note name, or `---` for an empty step. It is followed
+//! by a token for each value that differs from that of a new note (or from 0, for empty steps).
+//! Printing and parsing are exact inverses of each other.
+//!
+//! Audio step tokens:
+//! - `S002`: sample (always present on notes)
+//! - `V+2.0dB`: volume, or `V-inf`
+//! - `P3R`, `P10L`: pan in %
+//! - `LP50`, `HP20`: filter cutoff in %
+//! - `RES40`: filter resonance in %
+//! - `OD25`: overdrive in %
+//! - `B8`: bit depth
+//! - `REV4`, `DLY5`: reverb and delay sends in %
+//! - `ST2880`, `END14719`: sample start and end, from 0 to 32767
+//! - `ATK10`, `DEC75`: sample attack and decay in %
+//! - `T-12.5c`: micro tune in cents
+//! - `M+10`: micro move in 24ths of a step
+//! - `F3`: sample folder
+//! - `RPT2/9`: repeat type and grid
+//! - `CH11/0`: chance type and action
+//! - `N60`: the note number of an empty step
+//! - `X:2a00100118ffff7f`: the undecoded trailing bytes, in hex
+//!
+//! MIDI step tokens:
+//! - `V100`: velocity
+//! - `L60`: note length in 60ths of a quarter note
+//! - `J2`, `U1`: Jack or USB channel
+//! - `PG5`: program
+//! - `CHD1`: chord
+//! - `PB+1c`: pitch bend in cents
+//! - `CC74:104`: the value of each CC that is set
+//! - `M`, `RPT`, `CH`, `N` and `X:`, as for audio steps
+//! - `XV:2a00…`: the stored values of the parameters that aren't set, in hex
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::units::{note_name, parse_note_name};
+use crate::{MidiChannel, MidiStep, ParseError, Result, Step, Track, TrackStep};
+
+/// The trailing bytes of a new note
+const NOTE_REST: [u8; 8] = [0x2A, 0x00, 0x10, 0x01, 0x18, 0xFF, 0xFF, 0x7F];
+
+// Longer prefixes come first, so that they aren't mistaken for shorter ones
+const STEP_PREFIXES: [&str; 21] = [
+    "X:", "RES", "REV", "RPT", "DLY", "DEC", "END", "ATK", "ST", "OD", "LP", "HP", "CH", "S", "V",
+    "P", "B", "T", "M", "F", "N",
+];
+const MIDI_STEP_PREFIXES: [&str; 14] = [
+    "XV:", "X:", "RPT", "CHD", "CC", "CH", "PG", "PB", "V", "L", "J", "U", "M", "N",
+];
+
+fn invalid(token: &str) -> ParseError {
+    ParseError(format!("Invalid token `{}`", token))
+}
+
+/// `value` in units of 10^-`decimals`, without trailing zeros: `decimal(250, 2)` is `2.5`
+fn decimal(value: i64, decimals: u32) -> String {
+    let scale = 10i64.pow(decimals);
+    let sign = if value < 0 { "-" } else { "" };
+    let (int, frac) = (value.abs() / scale, value.abs() % scale);
+    if frac == 0 {
+        format!("{}{}", sign, int)
+    } else {
+        let frac = format!("{:0width$}", frac, width = decimals as usize);
+        format!("{}{}.{}", sign, int, frac.trim_end_matches('0'))
+    }
+}
+
+fn signed_decimal(value: i64, decimals: u32) -> String {
+    if value < 0 {
+        decimal(value, decimals)
+    } else {
+        format!("+{}", decimal(value, decimals))
+    }
+}
+
+/// The inverse of [decimal]. Fails if there are more than `decimals` decimal places.
+fn parse_decimal(s: &str, decimals: u32) -> Option<i64> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    let digits = |x: &str| x.chars().all(|c| c.is_ascii_digit());
+    if int.is_empty() || !digits(int) || !digits(frac) || frac.len() > decimals as usize {
+        return None;
+    }
+    let frac = format!("{:0<width$}", frac, width = decimals as usize);
+    let value = int.parse::<i64>().ok()? * 10i64.pow(decimals)
+        + if frac.is_empty() {
+            0
+        } else {
+            frac.parse::<i64>().ok()?
+        };
+    Some(if negative { -value } else { value })
+}
+
+fn parse<T: FromStr>(s: &str, token: &str) -> Result<T> {
+    s.parse().map_err(|_| invalid(token))
+}
+
+/// Parse a value printed with [decimal] and convert it to `T`
+fn parse_scaled<T: TryFrom<i64>>(s: &str, decimals: u32, token: &str) -> Result<T> {
+    parse_decimal(s, decimals)
+        .and_then(|v| T::try_from(v).ok())
+        .ok_or_else(|| invalid(token))
+}
+
+/// Two numbers separated by a `/`
+fn parse_pair(s: &str, token: &str) -> Result<(u16, u16)> {
+    let (a, b) = s.split_once('/').ok_or_else(|| invalid(token))?;
+    Ok((parse(a, token)?, parse(b, token)?))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(s: &str, token: &str) -> Result<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(invalid(token));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| invalid(token)))
+        .collect()
+}
+
+/// Split a token into its prefix and value
+fn split_token<'a>(token: &'a str, prefixes: &[&'static str]) -> Result<(&'static str, &'a str)> {
+    prefixes
+        .iter()
+        .find_map(|&p| token.strip_prefix(p).map(|value| (p, value)))
+        .ok_or_else(|| invalid(token))
+}
+
+/// The step number, whether there's a note, the note, and the remaining tokens of a line
+fn parse_line(line: &str) -> Result<(usize, bool, u8, Vec<&str>)> {
+    let mut words = line.split_whitespace();
+    let number = words
+        .next()
+        .ok_or_else(|| ParseError("Empty step line".to_string()))?;
+    let number = parse(number, number)?;
+    let note = words
+        .next()
+        .ok_or_else(|| ParseError(format!("Missing note in step line `{}`", line)))?;
+    if note == "---" {
+        Ok((number, false, 0, words.collect()))
+    } else {
+        let n = parse_note_name(note).ok_or_else(|| invalid(note))?;
+        Ok((number, true, n, words.collect()))
+    }
+}
+
+fn check_empty(is_empty: bool, has_note: bool, line: &str) -> Result<()> {
+    if is_empty == has_note {
+        return Err(ParseError(format!(
+            "The trailing bytes of `{}` don't match whether it has a note",
+            line
+        )));
+    }
+    Ok(())
+}
+
+impl Step {
+    /// The values of a new note, or an empty step
+    pub(crate) fn baseline(number: usize, note: Option<u8>) -> Self {
+        let is_note = note.is_some();
+        Self {
+            number,
+            sample: 0,
+            note: note.unwrap_or(0),
+            volume: if is_note { 7600 } else { 0 },
+            pan: 0,
+            filter_cutoff: 0,
+            filter_resonance: 0,
+            overdrive: 0,
+            bit_depth: if is_note { 16 } else { 0 },
+            micro_move: 0,
+            reverb: 0,
+            delay: 0,
+            sample_start: 0,
+            sample_end: if is_note { 32767 } else { 0 },
+            sample_attack: 0,
+            sample_decay: 0,
+            sample_folder: 0,
+            repeat_type: 0,
+            repeat_grid: 0,
+            chance_type: 0,
+            chance_action: 0,
+            micro_tune: 0,
+            rest: if is_note {
+                NOTE_REST.to_vec()
+            } else {
+                vec![0, 0]
+            },
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let is_note = !self.is_empty();
+        let base = Self::baseline(self.number, is_note.then_some(self.note));
+        let mut tokens = vec![format!("{:02}", self.number)];
+        if is_note {
+            tokens.push(note_name(self.note));
+            tokens.push(format!("S{:03}", self.sample));
+        } else {
+            tokens.push("---".to_string());
+            if self.note != base.note {
+                tokens.push(format!("N{}", self.note));
+            }
+            if self.sample != base.sample {
+                tokens.push(format!("S{:03}", self.sample));
+            }
+        }
+        if self.volume != base.volume {
+            tokens.push(match self.volume {
+                0 => "V-inf".to_string(),
+                // 200 = 1dB, so thousandths of a dB are exact
+                v => {
+                    let db = signed_decimal((v as i64 - 7600) * 5, 3);
+                    let point = if db.contains('.') { "" } else { ".0" };
+                    format!("V{}{}dB", db, point)
+                }
+            });
+        }
+        if self.pan != base.pan {
+            let side = if self.pan < 0 { "L" } else { "R" };
+            tokens.push(format!("P{}{}", decimal((self.pan as i64).abs(), 2), side));
+        }
+        if self.filter_cutoff != base.filter_cutoff {
+            let kind = if self.filter_cutoff < 0 { "LP" } else { "HP" };
+            let amount = decimal((self.filter_cutoff as i64).abs(), 2);
+            tokens.push(format!("{}{}", kind, amount));
+        }
+        let percents = [
+            (
+                "RES",
+                self.filter_resonance as i64,
+                base.filter_resonance as i64,
+            ),
+            ("OD", self.overdrive as i64, base.overdrive as i64),
+        ];
+        for (prefix, value, base) in percents {
+            if value != base {
+                tokens.push(format!("{}{}", prefix, decimal(value, 2)));
+            }
+        }
+        if self.bit_depth != base.bit_depth {
+            tokens.push(format!("B{}", self.bit_depth));
+        }
+        let percents = [
+            ("REV", self.reverb as i64, base.reverb as i64),
+            ("DLY", self.delay as i64, base.delay as i64),
+        ];
+        for (prefix, value, base) in percents {
+            if value != base {
+                tokens.push(format!("{}{}", prefix, decimal(value, 2)));
+            }
+        }
+        if self.sample_start != base.sample_start {
+            tokens.push(format!("ST{}", self.sample_start));
+        }
+        if self.sample_end != base.sample_end {
+            tokens.push(format!("END{}", self.sample_end));
+        }
+        let percents = [
+            ("ATK", self.sample_attack as i64, base.sample_attack as i64),
+            ("DEC", self.sample_decay as i64, base.sample_decay as i64),
+        ];
+        for (prefix, value, base) in percents {
+            if value != base {
+                tokens.push(format!("{}{}", prefix, decimal(value, 2)));
+            }
+        }
+        if self.micro_tune != base.micro_tune {
+            tokens.push(format!("T{}c", signed_decimal(self.micro_tune as i64, 2)));
+        }
+        if self.micro_move != base.micro_move {
+            tokens.push(format!("M{:+}", self.micro_move));
+        }
+        if self.sample_folder != base.sample_folder {
+            tokens.push(format!("F{}", self.sample_folder));
+        }
+        if (self.repeat_type, self.repeat_grid) != (base.repeat_type, base.repeat_grid) {
+            tokens.push(format!("RPT{}/{}", self.repeat_type, self.repeat_grid));
+        }
+        if (self.chance_type, self.chance_action) != (base.chance_type, base.chance_action) {
+            tokens.push(format!("CH{}/{}", self.chance_type, self.chance_action));
+        }
+        if self.rest != base.rest {
+            tokens.push(format!("X:{}", hex(&self.rest)));
+        }
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+impl FromStr for Step {
+    type Err = ParseError;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let (number, has_note, note, tokens) = parse_line(line)?;
+        let mut step = Self::baseline(number, has_note.then_some(note));
+        for token in tokens {
+            let (prefix, value) = split_token(token, &STEP_PREFIXES)?;
+            match prefix {
+                "S" => step.sample = parse(value, token)?,
+                "N" => step.note = parse(value, token)?,
+                "V" if value == "-inf" => step.volume = 0,
+                "V" => {
+                    let db = value.strip_suffix("dB").ok_or_else(|| invalid(token))?;
+                    let thousandths: i64 = parse_scaled(db, 3, token)?;
+                    if thousandths % 5 != 0 {
+                        return Err(invalid(token));
+                    }
+                    step.volume =
+                        u16::try_from(thousandths / 5 + 7600).map_err(|_| invalid(token))?;
+                }
+                "P" => {
+                    let (amount, sign) = if let Some(a) = value.strip_suffix('L') {
+                        (a, -1)
+                    } else {
+                        (value.strip_suffix('R').ok_or_else(|| invalid(token))?, 1)
+                    };
+                    step.pan = parse_scaled::<i16>(amount, 2, token)?
+                        .checked_mul(sign)
+                        .ok_or_else(|| invalid(token))?;
+                }
+                "LP" => {
+                    step.filter_cutoff = parse_scaled::<i16>(value, 2, token)?
+                        .checked_neg()
+                        .ok_or_else(|| invalid(token))?
+                }
+                "HP" => step.filter_cutoff = parse_scaled(value, 2, token)?,
+                "RES" => step.filter_resonance = parse_scaled(value, 2, token)?,
+                "OD" => step.overdrive = parse_scaled(value, 2, token)?,
+                "B" => step.bit_depth = parse(value, token)?,
+                "REV" => step.reverb = parse_scaled(value, 2, token)?,
+                "DLY" => step.delay = parse_scaled(value, 2, token)?,
+                "ST" => step.sample_start = parse(value, token)?,
+                "END" => step.sample_end = parse(value, token)?,
+                "ATK" => step.sample_attack = parse_scaled(value, 2, token)?,
+                "DEC" => step.sample_decay = parse_scaled(value, 2, token)?,
+                "T" => {
+                    let cents = value.strip_suffix('c').ok_or_else(|| invalid(token))?;
+                    step.micro_tune = parse_scaled(cents, 2, token)?;
+                }
+                "M" => step.micro_move = parse(value, token)?,
+                "F" => step.sample_folder = parse(value, token)?,
+                "RPT" => (step.repeat_type, step.repeat_grid) = parse_pair(value, token)?,
+                "CH" => (step.chance_type, step.chance_action) = parse_pair(value, token)?,
+                "X:" => step.rest = parse_hex(value, token)?,
+                _ => return Err(invalid(token)),
+            }
+        }
+        check_empty(step.is_empty(), has_note, line)?;
+        Ok(step)
+    }
+}
+
+impl MidiStep {
+    /// The values of a new note, or an empty step
+    pub(crate) fn baseline(number: usize, note: Option<u8>) -> Self {
+        let is_note = note.is_some();
+        let mut step = Self {
+            number,
+            channel: MidiChannel::Jack(1),
+            program: None,
+            note: note.unwrap_or(0),
+            velocity: if is_note { 100 } else { 0 },
+            note_length: if is_note { 60 } else { 0 },
+            chord: 0,
+            micro_move: 0,
+            pitch_bend: None,
+            cc12: None,
+            cc13: None,
+            cc17: None,
+            cc19: None,
+            cc22: None,
+            cc71: None,
+            cc74: None,
+            cc75: None,
+            repeat_type: 0,
+            repeat_grid: 0,
+            chance_type: 0,
+            chance_action: 0,
+            values: vec![],
+            rest: vec![0, 0],
+        };
+        if is_note {
+            step.rest = step.note_rest();
+        }
+        step
+    }
+
+    /// The trailing bytes of a note, with the bitmask of the values that are set
+    pub(crate) fn note_rest(&self) -> Vec<u8> {
+        let bit = |is_set: bool, bit: u8| if is_set { 1 << bit } else { 0 };
+        let m1 = 0xC3
+            | bit(self.cc12.is_some(), 5)
+            | bit(self.cc13.is_some(), 4)
+            | bit(self.cc71.is_some(), 3)
+            | bit(self.cc74.is_some(), 2);
+        let m2 = 0x8C
+            | bit(self.cc22.is_some(), 6)
+            | bit(self.pitch_bend.is_some(), 5)
+            | bit(self.cc17.is_some(), 1)
+            | bit(self.cc19.is_some(), 0);
+        let m3 = 0x70
+            | bit(self.repeat_grid != 0, 3)
+            | bit(self.repeat_type != 0, 2)
+            | bit(self.program.is_some(), 1)
+            | bit(self.cc75.is_some(), 0);
+        vec![0x00, 0x00, 0x10, 0x01, 0x18, m1, m2, m3]
+    }
+
+    fn ccs(&self) -> [(u8, Option<u8>); 8] {
+        [
+            (12, self.cc12),
+            (13, self.cc13),
+            (17, self.cc17),
+            (19, self.cc19),
+            (22, self.cc22),
+            (71, self.cc71),
+            (74, self.cc74),
+            (75, self.cc75),
+        ]
+    }
+
+    pub(crate) fn cc_mut(&mut self, cc: u8) -> Option<&mut Option<u8>> {
+        match cc {
+            12 => Some(&mut self.cc12),
+            13 => Some(&mut self.cc13),
+            17 => Some(&mut self.cc17),
+            19 => Some(&mut self.cc19),
+            22 => Some(&mut self.cc22),
+            71 => Some(&mut self.cc71),
+            74 => Some(&mut self.cc74),
+            75 => Some(&mut self.cc75),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MidiStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let is_note = !self.is_empty();
+        let base = Self::baseline(self.number, is_note.then_some(self.note));
+        let mut tokens = vec![format!("{:02}", self.number)];
+        if is_note {
+            tokens.push(note_name(self.note));
+        } else {
+            tokens.push("---".to_string());
+            if self.note != base.note {
+                tokens.push(format!("N{}", self.note));
+            }
+        }
+        if self.velocity != base.velocity {
+            tokens.push(format!("V{}", self.velocity));
+        }
+        if self.note_length != base.note_length {
+            tokens.push(format!("L{}", self.note_length));
+        }
+        if self.channel != base.channel {
+            tokens.push(match self.channel {
+                MidiChannel::Jack(n) => format!("J{}", n),
+                MidiChannel::Usb(n) => format!("U{}", n),
+            });
+        }
+        if let Some(program) = self.program {
+            tokens.push(format!("PG{}", program));
+        }
+        if self.chord != base.chord {
+            tokens.push(format!("CHD{}", self.chord));
+        }
+        if let Some(bend) = self.pitch_bend {
+            tokens.push(format!("PB{}c", signed_decimal(bend as i64, 2)));
+        }
+        for (cc, value) in self.ccs() {
+            if let Some(value) = value {
+                tokens.push(format!("CC{}:{}", cc, value));
+            }
+        }
+        if self.micro_move != base.micro_move {
+            tokens.push(format!("M{:+}", self.micro_move));
+        }
+        if (self.repeat_type, self.repeat_grid) != (base.repeat_type, base.repeat_grid) {
+            tokens.push(format!("RPT{}/{}", self.repeat_type, self.repeat_grid));
+        }
+        if (self.chance_type, self.chance_action) != (base.chance_type, base.chance_action) {
+            tokens.push(format!("CH{}/{}", self.chance_type, self.chance_action));
+        }
+        if !self.values.is_empty() {
+            tokens.push(format!("XV:{}", hex(&self.values)));
+        }
+        let rest = if is_note { self.note_rest() } else { base.rest };
+        if self.rest != rest {
+            tokens.push(format!("X:{}", hex(&self.rest)));
+        }
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+impl FromStr for MidiStep {
+    type Err = ParseError;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let (number, has_note, note, tokens) = parse_line(line)?;
+        let mut step = Self::baseline(number, has_note.then_some(note));
+        let mut rest = None;
+        for token in tokens {
+            let (prefix, value) = split_token(token, &MIDI_STEP_PREFIXES)?;
+            match prefix {
+                "N" => step.note = parse(value, token)?,
+                "V" => step.velocity = parse(value, token)?,
+                "L" => step.note_length = parse(value, token)?,
+                "J" => step.channel = MidiChannel::Jack(parse(value, token)?),
+                "U" => step.channel = MidiChannel::Usb(parse(value, token)?),
+                "PG" => step.program = Some(parse(value, token)?),
+                "CHD" => step.chord = parse(value, token)?,
+                "PB" => {
+                    let cents = value.strip_suffix('c').ok_or_else(|| invalid(token))?;
+                    step.pitch_bend = Some(parse_scaled(cents, 2, token)?);
+                }
+                "CC" => {
+                    let (cc, v) = value.split_once(':').ok_or_else(|| invalid(token))?;
+                    let cc: u8 = parse(cc, token)?;
+                    let v = parse(v, token)?;
+                    *step.cc_mut(cc).ok_or_else(|| invalid(token))? = Some(v);
+                }
+                "M" => step.micro_move = parse(value, token)?,
+                "RPT" => (step.repeat_type, step.repeat_grid) = parse_pair(value, token)?,
+                "CH" => (step.chance_type, step.chance_action) = parse_pair(value, token)?,
+                "XV:" => {
+                    step.values = parse_hex(value, token)?;
+                    if step.values.len() != 42 {
+                        return Err(invalid(token));
+                    }
+                }
+                "X:" => rest = Some(parse_hex(value, token)?),
+                _ => return Err(invalid(token)),
+            }
+        }
+        step.rest = match rest {
+            Some(rest) => rest,
+            None if has_note => step.note_rest(),
+            None => vec![0, 0],
+        };
+        check_empty(step.is_empty(), has_note, line)?;
+        Ok(step)
+    }
+}
+
+impl<S: TrackStep + Clone + fmt::Display> Track<S> {
+    /// The steps of the track in the text notation, one per line
+    pub fn steps_to_text(&self) -> String {
+        self.steps.iter().map(|s| format!("{}\n", s)).collect()
+    }
+}
+
+impl<S: TrackStep + Clone + FromStr<Err = ParseError>> Track<S> {
+    /// Replace the steps of the track with those parsed from the text notation. Blank lines are
+    /// ignored. The steps must be numbered in order from 0.
+    pub fn set_steps_from_text(&mut self, text: &str) -> Result<()> {
+        let mut steps = vec![];
+        for (i, line) in text.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+            let step = S::from_str(line)?;
+            if step.number() != i {
+                return Err(ParseError(format!(
+                    "Expected step {} but found `{}`",
+                    i, line
+                )));
+            }
+            steps.push(step);
+        }
+        if steps.is_empty() || steps.len() > 64 {
+            return Err(ParseError(format!(
+                "A track must have 1 to 64 steps, not {}",
+                steps.len()
+            )));
+        }
+        self.steps = steps;
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! Conversions from the raw values stored in project files to the units shown on the device.
+
+use crate::{MidiChannel, TrackSpeed};
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Name of a midi note number, where 60 is C4
+pub fn note_name(note: u8) -> String {
+    format!("{}{}", NOTE_NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
+
+/// The inverse of [note_name]
+pub fn parse_note_name(name: &str) -> Option<u8> {
+    let split = name.find(|c: char| c == '-' || c.is_ascii_digit())?;
+    let (pitch, octave) = name.split_at(split);
+    let pitch = NOTE_NAMES.iter().position(|&n| n == pitch)? as i32;
+    let octave: i32 = octave.parse().ok()?;
+    u8::try_from((octave + 1) * 12 + pitch).ok()
+}
+
+/// Volume in dB, or None for -inf dB
+pub fn volume_db(volume: u16) -> Option<f32> {
+    // 0dB at 7600; 200 = 1dB
+    (volume > 0).then(|| (volume as f32 - 7600.0) / 200.0)
+}
+
+/// E.g. `+2.0dB` or `-infdB`
+pub fn format_volume(volume: u16) -> String {
+    match volume_db(volume) {
+        Some(db) => format!("{:+.1}dB", db),
+        None => "-infdB".to_string(),
+    }
+}
+
+/// Values where 10000 is 100%
+pub fn percent(value: i32) -> f32 {
+    value as f32 / 100.0
+}
+
+/// E.g. `100L`, `C` or `3R`
+pub fn format_pan(pan: i16) -> String {
+    match pan {
+        0 => "C".to_string(),
+        p if p < 0 => format!("{}L", percent(-(p as i32))),
+        p => format!("{}R", percent(p as i32)),
+    }
+}
+
+/// E.g. `LP100`, `Off` or `HP50`
+pub fn format_filter(cutoff: i16) -> String {
+    match cutoff {
+        0 => "Off".to_string(),
+        c if c < 0 => format!("LP{}", percent(-(c as i32))),
+        c => format!("HP{}", percent(c as i32)),
+    }
+}
+
+/// E.g. `+11/24`
+pub fn format_micro_move(micro_move: i16) -> String {
+    format!("{:+}/24", micro_move)
+}
+
+/// Micro tune and pitch bend in cents, e.g. `-100c`
+pub fn format_cents(value: i16) -> String {
+    format!("{:+}c", percent(value as i32))
+}
+
+/// E.g. `1/1`, `8/1` or `Paused`
+pub fn format_track_speed(speed: TrackSpeed) -> String {
+    match speed {
+        TrackSpeed::Fraction(n, d) => format!("{}/{}", n, d),
+        TrackSpeed::Paused => "Paused".to_string(),
+    }
+}
+
+/// E.g. `Jack 1` or `USB 16`
+pub fn format_midi_channel(channel: MidiChannel) -> String {
+    match channel {
+        MidiChannel::Jack(n) => format!("Jack {}", n),
+        MidiChannel::Usb(n) => format!("USB {}", n),
+    }
+}
+
+/// Midi note length as shown on the device, where 60 is 1.0
+pub fn note_length(note_length: u16) -> f32 {
+    note_length as f32 / 60.0
+}
+
+// Only the enum values that have been observed in example projects are named; the rest are
+// shown by number.
+
+pub fn chance_type_name(value: u16) -> String {
+    match value {
+        0 => "Always".to_string(),
+        1 => "90%".to_string(),
+        42 => "Skip 4|Play 5".to_string(),
+        n => format!("#{}", n),
+    }
+}
+
+pub fn chance_action_name(value: u16) -> String {
+    match value {
+        0 => "Play step".to_string(),
+        1 => "Rnd note".to_string(),
+        9 => "Humanize".to_string(),
+        n => format!("#{}", n),
+    }
+}
+
+pub fn repeat_type_name(value: u16) -> String {
+    match value {
+        0 => "Off".to_string(),
+        1 => "Straight".to_string(),
+        17 => "Down and up".to_string(),
+        n => format!("#{}", n),
+    }
+}
+
+pub fn repeat_grid_name(value: u16) -> String {
+    match value {
+        0 => "2 hits|1 step".to_string(),
+        15 => "32 hits|8 steps".to_string(),
+        n => format!("#{}", n),
+    }
+}
+
+pub fn chord_name(value: i16) -> String {
+    match value {
+        0 => "None".to_string(),
+        1 => "Power 4th".to_string(),
+        20 => "Minor 13th".to_string(),
+        n => format!("#{}", n),
+    }
+}
@@ -0,0 +1,331 @@
+//! Checks that the values of a project are within the ranges the device accepts.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::RangeInclusive;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::render::sample_slot;
+use crate::vfs::{self, Vfs};
+use crate::{MidiChannel, MidiStep, Pattern, Project, Result, Step, Track, TrackStep};
+
+/// A problem found in a project
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Violation {
+    /// Where the problem is, e.g. `pattern 0 audio track 1 variation 2 step 3` or
+    /// `patterns/0-16-2.track`. Tracks and variations are numbered from 0, as in the names of
+    /// track files.
+    pub location: String,
+    /// The field with the problem, e.g. `bit_depth`
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {}", self.location, self.field, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    violations: Vec<Violation>,
+}
+
+impl Checker {
+    fn report(&mut self, location: &str, field: &str, message: String) {
+        self.violations.push(Violation {
+            location: location.to_string(),
+            field: field.to_string(),
+            message,
+        });
+    }
+
+    fn range(&mut self, location: &str, field: &str, value: i64, range: RangeInclusive<i64>) {
+        if !range.contains(&value) {
+            self.report(
+                location,
+                field,
+                format!(
+                    "is {}, outside of {}..={}",
+                    value,
+                    range.start(),
+                    range.end()
+                ),
+            );
+        }
+    }
+
+    /// Check that the sample of every note is in `samplesMetadata`, and in `files` if given
+    fn samples(&mut self, project: &Project, files: Option<&BTreeSet<u16>>) {
+        let slots = project.samples.slot_folders.len();
+        for pattern in &project.patterns {
+            for (n, variations) in pattern.audio_tracks.iter().enumerate() {
+                for track in variations.iter().flatten() {
+                    for step in track.steps.iter().filter(|s| !s.is_empty()) {
+                        let location = format!(
+                            "pattern {} audio track {} variation {} step {}",
+                            pattern.number, n, track.variation, step.number
+                        );
+                        if step.sample as usize >= slots {
+                            self.report(
+                                &location,
+                                "sample",
+                                format!("is slot {}, which is not in samplesMetadata", step.sample),
+                            );
+                        } else if files.is_some_and(|f| !f.contains(&step.sample)) {
+                            self.report(
+                                &location,
+                                "sample",
+                                format!("is slot {}, which has no file in samples/", step.sample),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check the names of the files in the patterns directory, which are otherwise ignored
+    /// when they don't match a track
+    fn track_files(&mut self, names: &[String]) {
+        for name in names {
+            let Some((pattern, track, variation)) = vfs::track_numbers(name) else {
+                continue;
+            };
+            let location = format!("patterns/{}", name);
+            let index = |n: usize| i64::try_from(n).unwrap_or(i64::MAX);
+            self.range(&location, "track", index(track), 0..=15);
+            self.range(&location, "variation", index(variation), 0..=15);
+            if !names.contains(&format!("{}.pattern", pattern)) {
+                self.report(
+                    &location,
+                    "pattern",
+                    format!("is {}, which has no pattern file", pattern),
+                );
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        let location = format!("pattern {}", pattern.number);
+        self.variations(&location, "audio", &pattern.audio_tracks, Self::step);
+        self.variations(&location, "MIDI", &pattern.midi_tracks, Self::midi_step);
+    }
+
+    fn variations<S: TrackStep + Clone>(
+        &mut self,
+        location: &str,
+        kind: &str,
+        tracks: &[[Option<Track<S>>; 16]; 8],
+        check_step: fn(&mut Self, &str, &S),
+    ) {
+        for (n, variations) in tracks.iter().enumerate() {
+            let location = format!("{} {} track {}", location, kind, n);
+            match variations.iter().flatten().filter(|t| t.is_default).count() {
+                0 => self.report(
+                    &location,
+                    "is_default",
+                    "no variation is active".to_string(),
+                ),
+                1 => (),
+                c => self.report(
+                    &location,
+                    "is_default",
+                    format!("{} variations are active", c),
+                ),
+            }
+            for (v, track) in variations.iter().enumerate() {
+                if let Some(track) = track {
+                    let location = format!("{} variation {}", location, v);
+                    self.range(
+                        &location,
+                        "number",
+                        track.number as i64,
+                        n as i64..=n as i64,
+                    );
+                    self.range(
+                        &location,
+                        "variation",
+                        track.variation as i64,
+                        v as i64..=v as i64,
+                    );
+                    self.track(&location, track, check_step);
+                }
+            }
+        }
+    }
+
+    fn track<S: TrackStep + Clone>(
+        &mut self,
+        location: &str,
+        track: &Track<S>,
+        check_step: fn(&mut Self, &str, &S),
+    ) {
+        self.range(location, "num_steps", track.steps.len() as i64, 1..=64);
+        self.range(location, "swing", track.swing as i64, 25..=75);
+        for (i, step) in track.steps.iter().enumerate() {
+            check_step(self, &format!("{} step {}", location, i), step);
+        }
+    }
+
+    fn step(&mut self, location: &str, step: &Step) {
+        // The values of empty steps aren't used
+        if step.is_empty() {
+            return;
+        }
+        let percent = 0..=10000;
+        let bipolar = -10000..=10000;
+        self.range(location, "note", step.note as i64, 12..=119);
+        self.range(location, "bit_depth", step.bit_depth as i64, 4..=16);
+        self.range(location, "pan", step.pan as i64, bipolar.clone());
+        self.range(
+            location,
+            "filter_cutoff",
+            step.filter_cutoff as i64,
+            bipolar.clone(),
+        );
+        self.range(
+            location,
+            "filter_resonance",
+            step.filter_resonance as i64,
+            percent.clone(),
+        );
+        self.range(
+            location,
+            "overdrive",
+            step.overdrive as i64,
+            percent.clone(),
+        );
+        self.range(location, "reverb", step.reverb as i64, percent.clone());
+        self.range(location, "delay", step.delay as i64, percent.clone());
+        self.range(
+            location,
+            "sample_start",
+            step.sample_start as i64,
+            0..=32767,
+        );
+        self.range(location, "sample_end", step.sample_end as i64, 0..=32767);
+        self.range(
+            location,
+            "sample_attack",
+            step.sample_attack as i64,
+            percent.clone(),
+        );
+        self.range(location, "sample_decay", step.sample_decay as i64, percent);
+        self.range(location, "micro_tune", step.micro_tune as i64, bipolar);
+        self.range(location, "micro_move", step.micro_move as i64, -11..=11);
+    }
+
+    fn midi_step(&mut self, location: &str, step: &MidiStep) {
+        if step.is_empty() {
+            return;
+        }
+        let midi = 0..=127;
+        self.range(location, "note", step.note as i64, midi.clone());
+        self.range(location, "velocity", step.velocity as i64, midi.clone());
+        let channel = match step.channel {
+            MidiChannel::Jack(n) | MidiChannel::Usb(n) => n,
+        };
+        self.range(location, "channel", channel as i64, 1..=16);
+        if let Some(program) = step.program {
+            self.range(location, "program", program as i64, midi.clone());
+        }
+        if let Some(bend) = step.pitch_bend {
+            self.range(location, "pitch_bend", bend as i64, -10000..=10000);
+        }
+        let ccs = [
+            ("cc12", step.cc12),
+            ("cc13", step.cc13),
+            ("cc17", step.cc17),
+            ("cc19", step.cc19),
+            ("cc22", step.cc22),
+            ("cc71", step.cc71),
+            ("cc74", step.cc74),
+            ("cc75", step.cc75),
+        ];
+        for (field, value) in ccs {
+            if let Some(value) = value {
+                self.range(location, field, value as i64, midi.clone());
+            }
+        }
+        self.range(location, "micro_move", step.micro_move as i64, -11..=11);
+    }
+
+    fn ranges(&mut self, project: &Project) {
+        let settings = &project.settings;
+        for (kind, mappings) in [
+            ("Jack", &settings.jack_cc_mapping),
+            ("USB", &settings.usb_cc_mapping),
+        ] {
+            for (i, m) in mappings.iter().enumerate() {
+                let location = format!("settings {} CC mapping {}", kind, i + 1);
+                let ccs = [
+                    ("cutoff", m.cutoff),
+                    ("resonance", m.resonance),
+                    ("sample_attack", m.sample_attack),
+                    ("sample_decay", m.sample_decay),
+                    ("reverb_send", m.reverb_send),
+                    ("delay_send", m.delay_send),
+                    ("overdrive", m.overdrive),
+                    ("bit_depth", m.bit_depth),
+                ];
+                for (field, cc) in ccs {
+                    self.range(&location, field, cc as i64, 0..=127);
+                }
+            }
+        }
+        for pattern in &project.patterns {
+            self.pattern(pattern);
+        }
+    }
+}
+
+impl Project {
+    /// Check that every value is within the range the device accepts, and that each track has
+    /// exactly one active variation. Empty steps are not checked.
+    pub fn check_ranges(&self) -> Vec<Violation> {
+        let mut checker = Checker::default();
+        checker.ranges(self);
+        checker.violations
+    }
+
+    /// Find problems that would stop the project from playing as expected on the device: the
+    /// problems found by [check_ranges](Self::check_ranges), and notes whose sample slot is not
+    /// in `samplesMetadata`
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut checker = Checker::default();
+        checker.ranges(self);
+        checker.samples(self, None);
+        checker.violations
+    }
+
+    /// Read the project in the directory `path` and [validate](Self::validate) it. Also report
+    /// notes whose sample has no file in `samples/`, and track files that don't match a track
+    /// of a pattern.
+    #[cfg(feature = "fs")]
+    pub fn validate_dir(path: &Path) -> Result<Vec<Violation>> {
+        Self::validate_files(path)
+    }
+
+    /// As [validate_dir](Self::validate_dir), for the files of a project wherever they are
+    /// stored
+    pub fn validate_files(files: &(impl Vfs + ?Sized)) -> Result<Vec<Violation>> {
+        let project = Self::from_files(files)?;
+        let samples: BTreeSet<u16> = vfs::list_dir(files, "samples")?
+            .iter()
+            .filter_map(|name| sample_slot(name))
+            .collect();
+
+        let mut checker = Checker::default();
+        checker.ranges(&project);
+        checker.samples(&project, Some(&samples));
+        checker.track_files(&vfs::list_dir(files, "patterns")?);
+        Ok(checker.violations)
+    }
+}
@@ -0,0 +1,439 @@
+//! Access to the files of a project, wherever they are stored: a directory, files held in
+//! memory such as an upload, or a zip archive.
+
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "fs")]
+use std::fs;
+use std::io;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+use crate::{MidiStep, ParseError, Pattern, Project, Result, Samples, Settings, Step, Track};
+
+/// The files of a project.
+///
+/// Paths are relative to the project directory and separated by `/`, e.g.
+/// `patterns/0.pattern`. Implemented for a project directory, for maps from paths to file
+/// contents, and for zip archives with the `zip` feature.
+pub trait Vfs {
+    /// The contents of the file at `path`, or None if there is no such file
+    fn read_file(&self, path: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// The names of the files in the directory `dir`, which are empty if there is no such
+    /// directory
+    fn list_dir(&self, dir: &str) -> io::Result<Vec<String>>;
+
+    /// Write the file at `path`, replacing it if it exists, and creating its directory if
+    /// needed
+    fn write_file(&mut self, path: &str, bytes: &[u8]) -> io::Result<()>;
+}
+
+impl<T: Vfs + ?Sized> Vfs for &mut T {
+    fn read_file(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        (**self).read_file(path)
+    }
+
+    fn list_dir(&self, dir: &str) -> io::Result<Vec<String>> {
+        (**self).list_dir(dir)
+    }
+
+    fn write_file(&mut self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        (**self).write_file(path, bytes)
+    }
+}
+
+#[cfg(feature = "fs")]
+fn write_in(dir: &Path, path: &str, bytes: &[u8]) -> io::Result<()> {
+    let path = dir.join(path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, bytes)
+}
+
+#[cfg(feature = "fs")]
+impl Vfs for Path {
+    fn read_file(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.join(path)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn list_dir(&self, dir: &str) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(self.join(dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let mut names = vec![];
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            // Project files have ASCII names, so names that aren't Unicode can be left out
+            if let Ok(name) = entry.file_name().into_string() {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    fn write_file(&mut self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        write_in(self, path, bytes)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Vfs for PathBuf {
+    fn read_file(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        self.as_path().read_file(path)
+    }
+
+    fn list_dir(&self, dir: &str) -> io::Result<Vec<String>> {
+        self.as_path().list_dir(dir)
+    }
+
+    fn write_file(&mut self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        write_in(self, path, bytes)
+    }
+}
+
+/// The names of the files directly in `dir`, out of a list of file paths
+pub(crate) fn names_in<'a>(paths: impl Iterator<Item = &'a String>, dir: &str) -> Vec<String> {
+    let prefix = join(dir.trim_end_matches('/'), "");
+    paths
+        .filter_map(|path| path.strip_prefix(&prefix))
+        .filter(|name| !name.contains('/'))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+impl Vfs for BTreeMap<String, Vec<u8>> {
+    fn read_file(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.get(path).cloned())
+    }
+
+    fn list_dir(&self, dir: &str) -> io::Result<Vec<String>> {
+        Ok(names_in(self.keys(), dir))
+    }
+
+    fn write_file(&mut self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        self.insert(path.to_string(), bytes.to_vec());
+        Ok(())
+    }
+}
+
+impl Vfs for HashMap<String, Vec<u8>> {
+    fn read_file(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.get(path).cloned())
+    }
+
+    fn list_dir(&self, dir: &str) -> io::Result<Vec<String>> {
+        Ok(names_in(self.keys(), dir))
+    }
+
+    fn write_file(&mut self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        self.insert(path.to_string(), bytes.to_vec());
+        Ok(())
+    }
+}
+
+/// The files of a project by their path in the project directory, out of the files of an
+/// upload or archive named `source`.
+///
+/// The project may be at the top of the files, or in a folder, as when a project directory is
+/// zipped. Hidden files, and the files that macOS adds to archives, are left out.
+#[cfg(any(feature = "zip", feature = "wasm"))]
+pub(crate) fn project_files(
+    files: BTreeMap<String, Vec<u8>>,
+    source: &str,
+) -> Result<BTreeMap<String, Vec<u8>>> {
+    let files: BTreeMap<String, Vec<u8>> = files
+        .into_iter()
+        .filter(|(name, _)| {
+            !name
+                .split('/')
+                .any(|part| part.starts_with('.') || part == "__MACOSX")
+        })
+        .collect();
+    let prefix = if files.contains_key("settings") {
+        String::new()
+    } else {
+        let folders: Vec<&str> = files
+            .keys()
+            .filter_map(|name| name.strip_suffix("/settings"))
+            .filter(|folder| !folder.contains('/'))
+            .collect();
+        match folders[..] {
+            [folder] => join(folder, ""),
+            [] => {
+                return Err(ParseError(format!(
+                    "No settings file present in {}",
+                    source
+                )))
+            }
+            _ => return Err(ParseError(format!("More than one project in {}", source))),
+        }
+    };
+    Ok(files
+        .into_iter()
+        .filter_map(|(name, bytes)| Some((name.strip_prefix(&prefix)?.to_string(), bytes)))
+        .collect())
+}
+
+/// The path of `name` in the directory `dir`, which is the root when empty
+pub(crate) fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+/// A file name part made of digits, as a number. Numbers too large to hold are `usize::MAX`.
+fn number(digits: &str) -> Option<usize> {
+    (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| digits.parse().unwrap_or(usize::MAX))
+}
+
+/// The number of a file named `N.pattern`
+pub(crate) fn pattern_number(name: &str) -> Option<u8> {
+    number(name.strip_suffix(".pattern")?)?.try_into().ok()
+}
+
+/// The pattern, track and variation numbers of a file named `P-T-V.track`
+pub(crate) fn track_numbers(name: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = name.strip_suffix(".track")?.split('-');
+    let numbers = (
+        number(parts.next()?)?,
+        number(parts.next()?)?,
+        number(parts.next()?)?,
+    );
+    parts.next().is_none().then_some(numbers)
+}
+
+pub(crate) fn list_dir(files: &(impl Vfs + ?Sized), dir: &str) -> Result<Vec<String>> {
+    let mut names = files
+        .list_dir(dir)
+        .map_err(|e| ParseError(format!("Cannot read {} dir: {}", dir, e)))?;
+    // In the order that reading a directory gives
+    names.sort();
+    Ok(names)
+}
+
+pub(crate) fn read_file(files: &(impl Vfs + ?Sized), path: &str) -> Result<Vec<u8>> {
+    files
+        .read_file(path)
+        .map_err(|e| ParseError(format!("Cannot read {}: {}", path, e)))?
+        .ok_or_else(|| ParseError(format!("No {} file present", path)))
+}
+
+pub(crate) fn write_file(files: &mut (impl Vfs + ?Sized), path: &str, bytes: &[u8]) -> Result<()> {
+    files
+        .write_file(path, bytes)
+        .map_err(|e| ParseError(format!("Cannot write {}: {}", path, e)))
+}
+
+/// Add the path of the file being parsed to a parse error
+pub(crate) fn in_file<T>(result: Result<T>, path: &str) -> Result<T> {
+    result.map_err(|e| ParseError(format!("Cannot parse {}: {}", path, e.0)))
+}
+
+/// Every value, or every error. Several errors are combined into one, so that all of the files
+/// that can't be read are reported.
+pub(crate) fn collect_all<T>(results: impl IntoIterator<Item = Result<T>>) -> Result<Vec<T>> {
+    let mut values = vec![];
+    let mut errors = vec![];
+    for result in results {
+        match result {
+            Ok(value) => values.push(value),
+            Err(e) => errors.push(e),
+        }
+    }
+    match errors.len() {
+        0 => Ok(values),
+        1 => Err(errors.remove(0)),
+        n => Err(ParseError(format!(
+            "{} files cannot be read: {}",
+            n,
+            errors
+                .into_iter()
+                .map(|e| e.0)
+                .collect::<Vec<String>>()
+                .join("; ")
+        ))),
+    }
+}
+
+/// The name and number of each pattern file, out of the names of the files in a directory
+pub(crate) fn pattern_files(names: &[String]) -> Vec<(&str, u8)> {
+    names
+        .iter()
+        .filter_map(|name| Some((name.as_str(), pattern_number(name)?)))
+        .collect()
+}
+
+/// The name, track and variation of each track file of the pattern `number`, out of the names
+/// of the files in a directory. Files that don't match a track are left out.
+pub(crate) fn track_files(names: &[String], number: u8) -> Vec<(&str, usize, usize)> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let (p, track, variation) = track_numbers(name)?;
+            (p == number as usize && track < 16 && variation < 16).then_some((
+                name.as_str(),
+                track,
+                variation,
+            ))
+        })
+        .collect()
+}
+
+/// A variation read from a track file
+pub(crate) enum Variation {
+    Audio(Track<Step>),
+    Midi(Track<MidiStep>),
+}
+
+/// Read the track file `name` in the directory `dir`
+pub(crate) fn read_variation(
+    files: &(impl Vfs + ?Sized),
+    dir: &str,
+    (name, track, variation): (&str, usize, usize),
+) -> Result<Variation> {
+    let path = join(dir, name);
+    let bytes = read_file(files, &path)?;
+    Ok(if track < 8 {
+        Variation::Audio(in_file(Track::from_bytes(&bytes, track, variation), &path)?)
+    } else {
+        Variation::Midi(in_file(
+            Track::from_bytes(&bytes, track - 8, variation),
+            &path,
+        )?)
+    })
+}
+
+impl Pattern {
+    /// The name and number of each pattern file in the directory `dir`
+    #[cfg(feature = "fs")]
+    pub(crate) fn pattern_files(
+        files: &(impl Vfs + ?Sized),
+        dir: &str,
+    ) -> Result<Vec<(String, u8)>> {
+        Ok(pattern_files(&list_dir(files, dir)?)
+            .into_iter()
+            .map(|(name, number)| (name.to_string(), number))
+            .collect())
+    }
+
+    /// Read the pattern file `name` in the directory `dir`, and the track files in `dir` that
+    /// match the pattern number
+    #[cfg(feature = "fs")]
+    pub(crate) fn read_in(
+        files: &(impl Vfs + ?Sized),
+        dir: &str,
+        name: &str,
+        number: u8,
+    ) -> Result<Self> {
+        Self::read_with(files, dir, &list_dir(files, dir)?, name, number)
+    }
+
+    /// As [read_in](Self::read_in), given the names of the files in `dir`
+    fn read_with(
+        files: &(impl Vfs + ?Sized),
+        dir: &str,
+        names: &[String],
+        name: &str,
+        number: u8,
+    ) -> Result<Self> {
+        let path = join(dir, name);
+        let mut pattern = in_file(Self::from_bytes(&read_file(files, &path)?, number), &path)?;
+        let variations = collect_all(
+            track_files(names, number)
+                .into_iter()
+                .map(|track_file| read_variation(files, dir, track_file)),
+        )?;
+        pattern.add_variation_files(variations);
+        Ok(pattern)
+    }
+
+    /// Read the pattern files in the directory `dir`, with their track files
+    pub(crate) fn read_all(files: &(impl Vfs + ?Sized), dir: &str) -> Result<Vec<Self>> {
+        let names = list_dir(files, dir)?;
+        collect_all(
+            pattern_files(&names)
+                .into_iter()
+                .map(|(name, number)| Self::read_with(files, dir, &names, name, number)),
+        )
+    }
+
+    pub(crate) fn add_variation_files(&mut self, variations: Vec<Variation>) {
+        let mut audio = vec![];
+        let mut midi = vec![];
+        for variation in variations {
+            match variation {
+                Variation::Audio(t) => audio.push(t),
+                Variation::Midi(t) => midi.push(t),
+            }
+        }
+        self.add_variations((audio, midi));
+    }
+
+    /// Read the pattern files in the directory `path`, in parallel with the `rayon` feature
+    #[cfg(feature = "rayon")]
+    #[cfg(feature = "fs")]
+    pub(crate) fn read_dir(path: &Path) -> Result<Vec<Self>> {
+        Self::read_all_par(path, "")
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    #[cfg(feature = "fs")]
+    pub(crate) fn read_dir(path: &Path) -> Result<Vec<Self>> {
+        Self::read_all(path, "")
+    }
+}
+
+impl Project {
+    /// Read the project in the directory `path`, in parallel with the `rayon` feature
+    #[cfg(feature = "rayon")]
+    #[cfg(feature = "fs")]
+    pub(crate) fn from_dir(path: &Path) -> Result<Self> {
+        Self::from_files_par(path)
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    #[cfg(feature = "fs")]
+    pub(crate) fn from_dir(path: &Path) -> Result<Self> {
+        Self::from_files(path)
+    }
+
+    /// Read a project from its files, wherever they are stored
+    pub fn from_files(files: &(impl Vfs + ?Sized)) -> Result<Self> {
+        let settings = Settings::from_bytes(&read_file(files, "settings")?)?;
+        let samples = Samples::from_bytes(&read_file(files, "samples/samplesMetadata")?)?;
+        // Empty projects may not have a patterns dir at all (e.g. when the project has been
+        // copied by a tool that drops empty directories)
+        let patterns = Pattern::read_all(files, "patterns")?;
+
+        Ok(Self {
+            settings,
+            samples,
+            patterns,
+        })
+    }
+
+    /// Write the project files, wherever they are stored. Sample audio files are not written.
+    pub fn write_files(&self, files: &mut (impl Vfs + ?Sized)) -> Result<()> {
+        write_file(files, "settings", &self.settings.to_bytes())?;
+        write_file(files, "samples/samplesMetadata", &self.samples.to_bytes())?;
+        for pattern in &self.patterns {
+            for (name, bytes) in pattern.files()? {
+                write_file(files, &join("patterns", &name), &bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+//! JavaScript bindings, for reading projects in the browser.
+//!
+//! Projects, patterns and tracks are returned as plain objects, with the fields of their Rust
+//! types.
+
+use std::collections::BTreeMap;
+
+use js_sys::{Array, Object, Uint8Array};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::vfs::project_files;
+use crate::{MidiStep, Pattern, Project, Step, Track};
+
+fn to_js(value: &impl Serialize) -> Result<JsValue, JsError> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+/// Parse a project from its files: an object from the path of each file to its contents as a
+/// `Uint8Array`. The paths may start with the name of the project folder, as with the files of
+/// a dropped directory. Sample audio files are ignored.
+#[wasm_bindgen(js_name = parseProject)]
+pub fn parse_project(files: &Object) -> Result<JsValue, JsError> {
+    let mut contents = BTreeMap::new();
+    for entry in Object::entries(files).iter() {
+        let entry: Array = entry.into();
+        let path = entry
+            .get(0)
+            .as_string()
+            .ok_or_else(|| JsError::new("File paths must be strings"))?;
+        if path.ends_with(".wav") {
+            continue;
+        }
+        contents.insert(path, Uint8Array::new(&entry.get(1)).to_vec());
+    }
+    to_js(&Project::from_files(&project_files(contents, "upload")?)?)
+}
+
+/// Parse the contents of a pattern file
+#[wasm_bindgen(js_name = parsePattern)]
+pub fn parse_pattern(bytes: &[u8], number: u8) -> Result<JsValue, JsError> {
+    to_js(&Pattern::from_bytes(bytes, number)?)
+}
+
+/// Parse the contents of a track file. Tracks are numbered as in the names of track files:
+/// 0-7 are audio tracks and 8-15 are MIDI tracks.
+#[wasm_bindgen(js_name = parseTrack)]
+pub fn parse_track(bytes: &[u8], track: usize, variation: usize) -> Result<JsValue, JsError> {
+    if track < 8 {
+        to_js(&Track::<Step>::from_bytes(bytes, track, variation)?)
+    } else if track < 16 {
+        to_js(&Track::<MidiStep>::from_bytes(bytes, track - 8, variation)?)
+    } else {
+        Err(JsError::new(&format!("No track {}", track)))
+    }
+}
@@ -0,0 +1,66 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The inverse of [Reader](crate::reader::Reader): accumulates the bytes of a file
+#[derive(Default)]
+pub struct Writer {
+    buffer: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, b: u8) {
+        self.buffer.push(b);
+    }
+
+    pub fn write_bytes(&mut self, bs: &[u8]) {
+        self.buffer.extend_from_slice(bs);
+    }
+
+    pub fn write_u16(&mut self, x: u16) {
+        let mut bs = [0; 2];
+        LittleEndian::write_u16(&mut bs, x);
+        self.write_bytes(&bs);
+    }
+
+    pub fn write_i16(&mut self, x: i16) {
+        self.write_u16(x as u16);
+    }
+
+    pub fn write_f32(&mut self, x: f32) {
+        let mut bs = [0; 4];
+        LittleEndian::write_f32(&mut bs, x);
+        self.write_bytes(&bs);
+    }
+
+    /// Write a string preceded by its length
+    pub fn write_string(&mut self, s: &str) {
+        self.write_variable_quantity(s.len());
+        self.write_bytes(s.as_bytes());
+    }
+
+    pub fn write_variable_quantity(&mut self, mut n: usize) {
+        loop {
+            let b = (n & 0b01111111) as u8;
+            n >>= 7;
+            if n == 0 {
+                self.write(b);
+                break;
+            }
+            self.write(b | 0b10000000);
+        }
+    }
+
+    /// Write a tag followed by the length of `bytes` and the bytes themselves
+    pub fn write_message(&mut self, tag: u8, bytes: &[u8]) {
+        self.write(tag);
+        self.write_variable_quantity(bytes.len());
+        self.write_bytes(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+}
@@ -0,0 +1,92 @@
+/* Reads the example projects through the C interface. Run by tests/ffi.rs. */
+
+#include <math.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "play_files.h"
+
+#define CHECK(cond)                                                       \
+    do {                                                                  \
+        if (!(cond)) {                                                    \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,        \
+                    __LINE__, #cond);                                     \
+            return 1;                                                     \
+        }                                                                 \
+    } while (0)
+
+static int test_demo(const char *examples) {
+    char path[1024];
+    snprintf(path, sizeof(path), "%s/The demo", examples);
+    PfProject *project = pf_project_open(path);
+    CHECK(project != NULL);
+    CHECK(strcmp(pf_project_name(project), "The demo") == 0);
+    CHECK(pf_project_bpm(project) > 0);
+    CHECK(pf_project_pattern_count(project) > 0);
+
+    size_t notes = 0;
+    for (size_t i = 0; i < pf_project_pattern_count(project); i++) {
+        const PfPattern *pattern = pf_project_pattern(project, i);
+        CHECK(pattern != NULL);
+        for (size_t t = 0; t < 8; t++) {
+            const PfTrack *track = pf_pattern_audio_track(pattern, t);
+            CHECK(track != NULL);
+            size_t count = pf_track_step_count(track);
+            CHECK(count >= 1 && count <= 64);
+            for (size_t s = 0; s < count; s++) {
+                const PfStep *step = pf_track_step(track, s);
+                CHECK(step != NULL);
+                if (pf_step_is_empty(step)) {
+                    continue;
+                }
+                notes++;
+                float volume = pf_step_volume_db(step);
+                CHECK(isinf(volume) || (volume > -100 && volume < 20));
+                CHECK(fabsf(pf_step_pan(step)) <= 100);
+                CHECK(pf_step_bit_depth(step) <= 16);
+            }
+            CHECK(pf_track_step(track, count) == NULL);
+
+            const PfMidiTrack *midi = pf_pattern_midi_track(pattern, t);
+            CHECK(midi != NULL);
+            for (size_t s = 0; s < pf_midi_track_step_count(midi); s++) {
+                const PfMidiStep *step = pf_midi_track_step(midi, s);
+                CHECK(pf_midi_step_channel(step) >= 1 &&
+                      pf_midi_step_channel(step) <= 16);
+            }
+        }
+        CHECK(pf_pattern_audio_track(pattern, 8) == NULL);
+    }
+    CHECK(notes > 0);
+    CHECK(pf_project_pattern(project, pf_project_pattern_count(project)) == NULL);
+    pf_project_free(project);
+    return 0;
+}
+
+static int test_errors(const char *examples) {
+    char path[1024];
+    snprintf(path, sizeof(path), "%s/no such project", examples);
+    CHECK(pf_project_open(path) == NULL);
+    CHECK(pf_last_error() != NULL);
+    CHECK(strstr(pf_last_error(), "no such project") != NULL);
+    CHECK(pf_project_open(NULL) == NULL);
+
+    /* Accessors accept NULL */
+    CHECK(pf_project_pattern_count(NULL) == 0);
+    CHECK(pf_track_step(NULL, 0) == NULL);
+    CHECK(pf_step_is_empty(NULL));
+    pf_project_free(NULL);
+    return 0;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s EXAMPLE_PROJECTS_DIR\n", argv[0]);
+        return 2;
+    }
+    if (test_demo(argv[1]) || test_errors(argv[1])) {
+        return 1;
+    }
+    printf("ok\n");
+    return 0;
+}
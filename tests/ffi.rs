@@ -0,0 +1,49 @@
+//! Builds and runs `tests/c/ffi_test.c` against the shared library, with the system C
+//! compiler (`cc`, or `$CC`).
+//!
+//! Tests are linked to the Rust library, so the shared library is built here.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_c_program() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--features", "ffi", "--target-dir"])
+        .arg(&target_dir)
+        .current_dir(root)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let lib_dir = target_dir.join("debug");
+    let out = target_dir.join("ffi_test");
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .arg(root.join("tests/c/ffi_test.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lplay_files", "-lm", "-Wall", "-Werror", "-o"])
+        .arg(&out)
+        .status()
+        .expect("Cannot run the C compiler");
+    assert!(status.success());
+
+    // Cargo's library path would take precedence over the rpath of the program
+    let output = Command::new(&out)
+        .env_remove("LD_LIBRARY_PATH")
+        .arg(root.join("examples/projects"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
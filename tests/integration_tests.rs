@@ -194,3 +194,1250 @@ fn test_variations() {
     assert!(TEST_1.patterns[0].midi_tracks[0][0].is_some());
     assert!(TEST_1.patterns[0].midi_tracks[0][1].is_some());
 }
+
+#[test]
+fn test_play_order() {
+    let pat = &TEST_1.patterns[0];
+    assert_eq!(
+        PlayMode::from(pat.audio_track(0).play_mode),
+        PlayMode::Forward
+    );
+    assert_eq!(
+        pat.audio_track(0).play_order(0),
+        (0..16).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        pat.audio_track(0).play_order(3),
+        (0..16).collect::<Vec<_>>()
+    );
+
+    assert_eq!(
+        PlayMode::from(pat.audio_track(5).play_mode),
+        PlayMode::Reverse
+    );
+    assert_eq!(
+        pat.audio_track(5).play_order(1),
+        (0..16).rev().collect::<Vec<_>>()
+    );
+
+    // Track 5 is 12 steps long in Thumper mode
+    let thumper = pat.audio_track(4);
+    assert_eq!(PlayMode::from(thumper.play_mode), PlayMode::Thumper);
+    assert_eq!(
+        thumper.play_order(0),
+        vec![0, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6]
+    );
+    assert_eq!(
+        thumper.play_order(1),
+        vec![0, 7, 0, 8, 0, 9, 0, 10, 0, 11, 0, 1]
+    );
+    assert_eq!(
+        thumper.play_steps(0).map(|s| s.number).collect::<Vec<_>>(),
+        thumper.play_order(0)
+    );
+
+    assert_eq!(PlayMode::PingPong.step_at(4, 4), 2);
+    assert_eq!(PlayMode::PingPong.step_at(6, 4), 0);
+    assert_eq!(PlayMode::Pendulum.step_at(4, 4), 3);
+    assert_eq!(PlayMode::Pendulum.step_at(7, 4), 0);
+    assert!((0..64).all(|p| PlayMode::Random.step_at(p, 12) < 12));
+    assert!(PlayMode::PingPong.is_approximate() && PlayMode::Random.is_approximate());
+    assert!(!PlayMode::Forward.is_approximate() && !PlayMode::Thumper.is_approximate());
+}
+
+#[test]
+fn test_units() {
+    assert_eq!(units::note_name(60), "C4");
+    assert_eq!(units::note_name(61), "C#4");
+    assert_eq!(units::note_name(12), "C0");
+    assert_eq!(units::note_name(119), "B8");
+    assert_eq!(units::note_name(0), "C-1");
+    assert_eq!(units::parse_note_name("C-1"), Some(0));
+    assert_eq!(units::parse_note_name("C#4"), Some(61));
+    assert_eq!(units::parse_note_name("H4"), None);
+    assert_eq!(units::format_volume(8000), "+2.0dB");
+    assert_eq!(units::format_volume(0), "-infdB");
+    assert_eq!(units::format_pan(-10000), "100L");
+    assert_eq!(units::format_pan(0), "C");
+    assert_eq!(units::format_filter(5000), "HP50");
+    assert_eq!(units::format_pan(i16::MIN), "327.68L");
+    assert_eq!(units::format_filter(i16::MIN), "LP327.68");
+    assert_eq!(units::format_micro_move(-11), "-11/24");
+
+    let step = &TEST_1.patterns[0].midi_track(0).steps[1];
+    assert_eq!(units::note_name(step.note), "G3");
+    assert_eq!(units::format_midi_channel(step.channel), "Jack 1");
+    assert_eq!(units::chord_name(step.chord), "Power 4th");
+}
+
+fn sine(frames: usize) -> render::AudioBuffer {
+    let mut audio = render::AudioBuffer::new(44100, 1, frames);
+    for (i, s) in audio.channels[0].iter_mut().enumerate() {
+        *s = (i as f32 * 440.0 * std::f32::consts::TAU / 44100.0).sin() * 0.5;
+    }
+    audio
+}
+
+#[test]
+fn test_render() {
+    let mut samples = render::SamplePool::default();
+    samples.insert(0, sine(44100));
+    let options = render::RenderOptions::default();
+    let pat = &TEST_1.patterns[0];
+    let bpm = TEST_1.settings.bpm;
+
+    // The longest tracks are 16 steps at a speed of 1/1
+    let loop_frames = (60.0 / bpm * 4.0 * 44100.0).ceil() as usize;
+    let audio = pat.render(bpm, &samples, &options).unwrap();
+    assert_eq!(audio.channels.len(), 2);
+    assert!((audio.frames() as i64 - loop_frames as i64).abs() <= 1);
+    assert!(audio.peak() > 0.1);
+
+    // Track 5: step 1 is panned hard left at -25dB, step 2 hard right and is played second in
+    // Thumper mode
+    let track = pat.render_track(4, bpm, &samples, &options).unwrap();
+    let step_frames = (60.0 / bpm / 4.0 * 44100.0) as usize;
+    let first = 0..step_frames - 100;
+    let second = step_frames + 100..step_frames * 2 - 100;
+    assert!(first.clone().all(|i| track.channels[1][i].abs() < 1e-6));
+    assert!(first.clone().any(|i| track.channels[0][i].abs() > 0.01));
+    assert!(second.clone().all(|i| track.channels[0][i].abs() < 1e-6));
+    assert!(second.clone().any(|i| track.channels[1][i].abs() > 0.1));
+
+    // Nothing is rendered without samples
+    let silent = pat
+        .render(bpm, &render::SamplePool::default(), &options)
+        .unwrap();
+    assert_eq!(silent.peak(), 0.0);
+
+    for bpm in [0.0, -120.0, f32::NAN] {
+        assert!(pat.render(bpm, &samples, &options).is_err());
+        assert!(pat.render_track(4, bpm, &samples, &options).is_err());
+    }
+
+    // A track without steps plays nothing
+    let mut pat = pat.clone();
+    let track = pat.audio_tracks[4]
+        .iter_mut()
+        .flatten()
+        .find(|t| t.is_default);
+    track.unwrap().steps.clear();
+    let track = pat.render_track(4, bpm, &samples, &options).unwrap();
+    assert_eq!(track.peak(), 0.0);
+}
+
+#[test]
+fn test_wav_round_trip() {
+    let dir = std::env::temp_dir().join("play-files-wav-round-trip");
+    std::fs::create_dir_all(&dir).unwrap();
+    let audio = sine(1000);
+    audio
+        .write_wav(&dir.join("[000] sine.wav"), render::SampleFormat::Float32)
+        .unwrap();
+    audio
+        .write_wav(&dir.join("[012] sine 16.wav"), render::SampleFormat::Int16)
+        .unwrap();
+
+    let samples = render::SamplePool::read(&dir).unwrap();
+    assert_eq!(samples.get(0), Some(&audio));
+    let int16 = samples.get(12).unwrap();
+    assert_eq!(int16.frames(), 1000);
+    assert!(int16.channels[0]
+        .iter()
+        .zip(&audio.channels[0])
+        .all(|(a, b)| (a - b).abs() < 1e-4));
+    assert_eq!(render::sample_slot("[123] My sample.wav"), Some(123));
+    assert_eq!(render::sample_slot("samplesMetadata"), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn saw(frames: usize) -> render::AudioBuffer {
+    let mut audio = render::AudioBuffer::new(44100, 1, frames);
+    for (i, s) in audio.channels[0].iter_mut().enumerate() {
+        *s = ((i as f32 * 220.0 / 44100.0).fract() * 2.0 - 1.0) * 0.25;
+    }
+    audio
+}
+
+/// Compare against a reference file in `tests/golden`. Run with `UPDATE_GOLDEN=1` to rewrite them.
+///
+/// The references were written by this renderer, not recorded from the device, so they only
+/// catch changes to its output. `test_render_impulse` checks the gains independently.
+fn assert_golden(name: &str, audio: &render::AudioBuffer) {
+    let path = Path::new("./tests/golden").join(name);
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        audio.write_wav(&path, render::SampleFormat::Int16).unwrap();
+    }
+    let golden = render::AudioBuffer::read_wav(&path).unwrap();
+    assert_eq!(golden.sample_rate, audio.sample_rate);
+    assert_eq!(golden.frames(), audio.frames(), "{}", name);
+    for (expected, actual) in golden.channels.iter().zip(&audio.channels) {
+        for (i, (e, a)) in expected.iter().zip(actual).enumerate() {
+            // The reference is clipped to full scale when it is written
+            let a = a.clamp(-1.0, 1.0);
+            assert!(
+                (e - a).abs() < 1e-3,
+                "{} differs at frame {}: {} != {}",
+                name,
+                i,
+                e,
+                a
+            );
+        }
+    }
+}
+
+#[test]
+fn test_render_impulse() {
+    // A single full-scale frame, so each note's output is its gain
+    let mut impulse = render::AudioBuffer::new(44100, 1, 100);
+    impulse.channels[0][0] = 1.0;
+    let mut samples = render::SamplePool::default();
+    samples.insert(0, impulse);
+    let pattern = Pattern::new(0).with_audio_track(
+        Track::new(0, 0)
+            .active()
+            .with_step(Step::new_note(0, 60))
+            .with_step(Step::new_note(4, 60).with_volume(6400).with_pan(-10000)),
+    );
+    // A step is 5512.5 frames at 120 bpm
+    let audio = pattern
+        .render(120.0, &samples, &render::RenderOptions::default())
+        .unwrap();
+    let assert_frame = |i: usize, left: f32, right: f32| {
+        let (l, r) = (audio.channels[0][i], audio.channels[1][i]);
+        assert!(
+            (l - left).abs() < 1e-5 && (r - right).abs() < 1e-5,
+            "frame {}: ({}, {}) != ({}, {})",
+            i,
+            l,
+            r,
+            left,
+            right
+        );
+    };
+
+    // 0dB in the center is unity on both sides
+    assert_frame(0, 1.0, 1.0);
+    assert_frame(1, 0.0, 0.0);
+    // -6dB panned hard left, with the left side at +3dB
+    assert_frame(22050, 10f32.powf(-6.0 / 20.0) * 2f32.sqrt(), 0.0);
+}
+
+#[test]
+fn test_render_effects() {
+    let mut samples = render::SamplePool::default();
+    samples.insert(0, saw(44100));
+    let options = render::RenderOptions {
+        sample_rate: 11025,
+        ..Default::default()
+    };
+    let pat = &TEST_1.patterns[0];
+    let bpm = TEST_1.settings.bpm;
+
+    // Track 5 has HP and LP filters; track 6 has overdrive and bit reduction
+    assert_golden(
+        "test 1 track 5.wav",
+        &pat.render_track(4, bpm, &samples, &options).unwrap(),
+    );
+    assert_golden(
+        "test 1 track 6.wav",
+        &pat.render_track(5, bpm, &samples, &options).unwrap(),
+    );
+    assert_golden("test 1.wav", &pat.render(bpm, &samples, &options).unwrap());
+}
+
+#[test]
+fn test_render_buses() {
+    // Track 4 sends step 1 to the reverb and step 2 to the delay
+    let mut samples = render::SamplePool::default();
+    samples.insert(21, saw(1000));
+    let pat = &TEST_1.patterns[0];
+    let bpm = TEST_1.settings.bpm;
+    let dry_options = render::RenderOptions {
+        tail: 1.0,
+        reverb: render::ReverbOptions {
+            level: 0.0,
+            ..Default::default()
+        },
+        delay: render::DelayOptions {
+            level: 0.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let loop_frames = (60.0 / bpm * 4.0 * 44100.0) as usize;
+    let tail = |audio: &render::AudioBuffer| audio.channels[0][loop_frames..].to_vec();
+
+    let dry = pat.render(bpm, &samples, &dry_options).unwrap();
+    assert!(tail(&dry).iter().all(|&s| s == 0.0));
+
+    let reverb = pat
+        .render(
+            bpm,
+            &samples,
+            &render::RenderOptions {
+                reverb: Default::default(),
+                ..dry_options.clone()
+            },
+        )
+        .unwrap();
+    assert!(tail(&reverb).iter().any(|&s| s.abs() > 1e-4));
+
+    // The delay repeats every 3 steps, so step 2 is heard again on step 5
+    let delay = pat
+        .render(
+            bpm,
+            &samples,
+            &render::RenderOptions {
+                delay: Default::default(),
+                ..dry_options.clone()
+            },
+        )
+        .unwrap();
+    let step_frames = 60.0 / bpm / 4.0 * 44100.0;
+    let mut echo = (step_frames * 4.0) as usize..(step_frames * 4.0) as usize + 900;
+    assert!(echo.any(|i| (delay.channels[0][i] - dry.channels[0][i]).abs() > 1e-3));
+    assert!(tail(&delay).iter().any(|&s| s.abs() > 1e-4));
+}
+
+#[test]
+fn test_render_stems() {
+    let mut samples = render::SamplePool::default();
+    samples.insert(0, saw(4410));
+    samples.insert(1, saw(4410));
+    samples.insert(21, saw(4410));
+    let options = render::StemOptions {
+        render: render::RenderOptions {
+            sample_rate: 11025,
+            tail: 0.5,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let stems = TEST_1.render_stems(0, &samples, &options).unwrap().unwrap();
+    assert_eq!(stems.tracks.len(), 8);
+    let frames = stems.tracks[0].1.frames();
+    assert!(stems.tracks.iter().all(|(_, t)| t.frames() == frames));
+    assert_eq!(stems.reverb.as_ref().unwrap().frames(), frames);
+    assert_eq!(stems.delay.as_ref().unwrap().frames(), frames);
+    assert_eq!(
+        stems.mix(),
+        TEST_1.patterns[0]
+            .render(TEST_1.settings.bpm, &samples, &options.render)
+            .unwrap()
+    );
+    assert_eq!(
+        stems.tracks[2].1,
+        TEST_1.patterns[0]
+            .render_track(2, TEST_1.settings.bpm, &samples, &options.render)
+            .unwrap()
+    );
+
+    // Muting track 4 removes its reverb and delay sends
+    let mut muted = render::StemOptions {
+        returns: true,
+        ..options.clone()
+    };
+    muted.muted[3] = true;
+    let stems = TEST_1.render_stems(0, &samples, &muted).unwrap().unwrap();
+    assert_eq!(stems.tracks.len(), 7);
+    assert!(stems.tracks.iter().all(|(n, _)| *n != 3));
+    assert_eq!(stems.reverb.unwrap().peak(), 0.0);
+
+    let mut soloed = render::StemOptions {
+        returns: false,
+        ..options.clone()
+    };
+    soloed.soloed[1] = true;
+    soloed.soloed[5] = true;
+    let stems = TEST_1.render_stems(0, &samples, &soloed).unwrap().unwrap();
+    assert_eq!(
+        stems.tracks.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+        vec![1, 5]
+    );
+    assert!(stems.reverb.is_none() && stems.delay.is_none());
+
+    // Nothing audible mixes to silence of the same length
+    let mut silent = options.clone();
+    silent.muted = [true; 8];
+    let mix = TEST_1
+        .render_stems(0, &samples, &silent)
+        .unwrap()
+        .unwrap()
+        .mix();
+    assert_eq!(mix.channels.len(), 2);
+    assert_eq!(mix.frames(), frames);
+    assert_eq!(mix.peak(), 0.0);
+
+    let dir = std::env::temp_dir().join("play-files-stems");
+    std::fs::create_dir_all(&dir).unwrap();
+    let paths = TEST_1.write_stems(0, &samples, &dir, &options).unwrap();
+    assert_eq!(paths.len(), 10);
+    assert!(dir.join("track 1.wav").is_file());
+    assert!(dir.join("reverb.wav").is_file());
+    assert_eq!(
+        render::AudioBuffer::read_wav(&dir.join("track 8.wav"))
+            .unwrap()
+            .frames(),
+        frames
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(TEST_1
+        .render_stems(1, &samples, &options)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_write_round_trip() {
+    let projects = [
+        ("blank", &*BLANK),
+        ("400 bpm", &*_400BPM),
+        ("c4 on 1", &*C4_ON_1),
+        ("empty notes on 1+3", &*EMPTY_NOTES_ON_1_3),
+        ("single empty note", &*SINGLE_EMPTY_NOTE),
+        ("sample st 2 trk 1+2", &*SAMPLE_ST_2_TRK_1_2),
+        ("test 1", &*TEST_1),
+        ("Believe It", &*BELIEVE_IT),
+        ("The demo", &*THE_DEMO),
+    ];
+    for (name, project) in projects {
+        let dir = std::env::temp_dir().join(format!("play-files-write {}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        project.write(&dir).unwrap();
+        assert_eq!(&Project::read(&dir).unwrap(), project, "{}", name);
+
+        let original = Path::new("./examples/projects").join(name);
+        for file in ["settings", "samples/samplesMetadata"] {
+            assert_eq!(
+                std::fs::read(dir.join(file)).unwrap(),
+                std::fs::read(original.join(file)).unwrap(),
+                "{} {}",
+                name,
+                file
+            );
+        }
+        // Git doesn't keep the empty patterns directories of the examples
+        let file_names = |dir: &Path| -> Vec<_> {
+            let mut names: Vec<_> = std::fs::read_dir(dir.join("patterns"))
+                .into_iter()
+                .flatten()
+                .map(|e| e.unwrap().file_name())
+                .collect();
+            names.sort();
+            names
+        };
+        let names = file_names(&dir);
+        assert_eq!(names, file_names(&original), "{}", name);
+        for file in names {
+            assert_eq!(
+                std::fs::read(dir.join("patterns").join(&file)).unwrap(),
+                std::fs::read(original.join("patterns").join(&file)).unwrap(),
+                "{} {:?}",
+                name,
+                file
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn test_check_ranges() {
+    assert_eq!(TEST_1.check_ranges(), vec![]);
+    assert_eq!(THE_DEMO.check_ranges(), vec![]);
+
+    let mut project = TEST_1.clone();
+    let track = project.patterns[0].audio_tracks[0][2].as_mut().unwrap();
+    track.steps[0].bit_depth = 20;
+    track.swing = 80;
+    let violations = project.check_ranges();
+    assert_eq!(violations.len(), 2);
+    assert_eq!(
+        violations[0].to_string(),
+        "pattern 0 audio track 0 variation 2: swing is 80, outside of 25..=75"
+    );
+    assert_eq!(violations[1].field, "bit_depth");
+    assert_eq!(
+        violations[1].location,
+        "pattern 0 audio track 0 variation 2 step 0"
+    );
+}
+
+#[test]
+fn test_validate() {
+    assert_eq!(SAMPLE_ST_2_TRK_1_2.validate(), vec![]);
+    let mut project = SAMPLE_ST_2_TRK_1_2.clone();
+    let track = project.patterns[0].audio_tracks[0][0].as_mut().unwrap();
+    track.steps[1].sample = 300;
+    track.steps[1].note = 5;
+    let violations: Vec<String> = project.validate().iter().map(|v| v.to_string()).collect();
+    assert_eq!(
+        violations,
+        vec![
+            "pattern 0 audio track 0 variation 0 step 1: note is 5, outside of 12..=119",
+            "pattern 0 audio track 0 variation 0 step 1: sample is slot 300, which is not in samplesMetadata",
+        ]
+    );
+
+    let dir = std::env::temp_dir().join("play-files-validate");
+    let _ = std::fs::remove_dir_all(&dir);
+    SAMPLE_ST_2_TRK_1_2.write(&dir).unwrap();
+    std::fs::write(dir.join("samples").join("[000] kick.wav"), []).unwrap();
+    let track = SAMPLE_ST_2_TRK_1_2.patterns[0].audio_track(0).to_bytes();
+    for name in ["0-16-1.track", "0-2-16.track", "3-1-1.track"] {
+        std::fs::write(dir.join("patterns").join(name), &track).unwrap();
+    }
+    let mut violations: Vec<String> = Project::validate_dir(&dir)
+        .unwrap()
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    // Track files are checked in directory order
+    violations.sort();
+    assert_eq!(
+        violations,
+        vec![
+            "pattern 0 audio track 1 variation 0 step 1: sample is slot 1, which has no file in samples/",
+            "patterns/0-16-1.track: track is 16, outside of 0..=15",
+            "patterns/0-2-16.track: variation is 16, outside of 0..=15",
+            "patterns/3-1-1.track: pattern is 3, which has no pattern file",
+        ]
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Files held in memory are checked the same way
+    let mut files = std::collections::BTreeMap::new();
+    SAMPLE_ST_2_TRK_1_2.write_files(&mut files).unwrap();
+    files.insert("patterns/3-1-1.track".to_string(), track);
+    let violations: Vec<String> = Project::validate_files(&files)
+        .unwrap()
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    assert_eq!(
+        violations,
+        vec![
+            "pattern 0 audio track 0 variation 0 step 1: sample is slot 0, which has no file in samples/",
+            "pattern 0 audio track 1 variation 0 step 1: sample is slot 1, which has no file in samples/",
+            "patterns/3-1-1.track: pattern is 3, which has no pattern file",
+        ]
+    );
+}
+
+#[test]
+fn test_step_text() {
+    let pat = &TEST_1.patterns[0];
+    let step = &pat.audio_track(0).steps[0];
+    assert_eq!(step.to_string(), "00 C4 S000 F65535 X:0000100118ffff7f");
+    assert_eq!(
+        pat.active_audio_track(0).unwrap().steps[1].to_string(),
+        "01 ---"
+    );
+
+    let step: Step = "03 C#4 S002 V+2.0dB P3R REV4 DLY5".parse().unwrap();
+    assert_eq!(step.number, 3);
+    assert_eq!(step.note, 61);
+    assert_eq!(step.sample, 2);
+    assert_eq!(step.volume, 8000);
+    assert_eq!(step.pan, 300);
+    assert_eq!(step.reverb, 400);
+    assert_eq!(step.delay, 500);
+    assert_eq!(step.to_string(), "03 C#4 S002 V+2.0dB P3R REV4 DLY5");
+    assert!("03 C#4 S002 V+2.0001dB".parse::<Step>().is_err());
+    assert!("03 C#4 Q1".parse::<Step>().is_err());
+    assert!("00 C4 S000 P-327.68L".parse::<Step>().is_err());
+    assert!("00 C4 S000 LP-327.68".parse::<Step>().is_err());
+
+    // Steps are written by position, so their numbers must be in order
+    let mut track = pat.audio_track(0).clone();
+    assert!(track
+        .set_steps_from_text("07 C4 S000\n07 C4 S000\n")
+        .is_err());
+    assert!(track
+        .set_steps_from_text("00 C4 S000\n02 C4 S000\n")
+        .is_err());
+    track.set_steps_from_text("00 C4 S000\n\n01 ---\n").unwrap();
+    assert_eq!(track.steps.len(), 2);
+
+    let step = &pat.midi_track(0).steps[1];
+    assert_eq!(
+        step.to_string(),
+        "01 G3 PG0 CHD1 PB-1c CC12:12 CC13:13 CC17:17 CC19:19 CC22:22 CC71:71 CC74:74 CC75:75 M+11 RPT1/0 CH1/1 X:0000100118ffef7f"
+    );
+
+    // Every step of every example survives a round trip
+    for project in [&*TEST_1, &*BELIEVE_IT, &*THE_DEMO, &*SAMPLE_ST_2_TRK_1_2] {
+        for pattern in &project.patterns {
+            for track in pattern.audio_tracks.iter().flatten().flatten() {
+                let text = track.steps_to_text();
+                let mut parsed = track.clone();
+                parsed.set_steps_from_text(&text).unwrap();
+                assert_eq!(&parsed, track, "{}", text);
+                assert_eq!(parsed.steps_to_text(), text);
+            }
+            for track in pattern.midi_tracks.iter().flatten().flatten() {
+                let text = track.steps_to_text();
+                let mut parsed = track.clone();
+                parsed.set_steps_from_text(&text).unwrap();
+                assert_eq!(&parsed, track, "{}", text);
+                assert_eq!(parsed.steps_to_text(), text);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_new_project() {
+    let project = Project::new("blank");
+    let dir = Path::new("./examples/projects/blank");
+    assert_eq!(
+        project.settings.to_bytes(),
+        std::fs::read(dir.join("settings")).unwrap()
+    );
+    assert_eq!(
+        project.samples.to_bytes(),
+        std::fs::read(dir.join("samples").join("samplesMetadata")).unwrap()
+    );
+    assert_eq!(project.check_ranges(), vec![]);
+
+    // Apart from its note on the first MIDI track, the device's pattern is a new one
+    let pattern = Pattern::new(0);
+    let mut device = C4_ON_1.patterns[0].clone();
+    device.midi_tracks[0] = pattern.midi_tracks[0].clone();
+    assert_eq!(device, pattern);
+
+    let track = pattern.audio_track(3);
+    assert_eq!(track.steps.len(), 16);
+    assert_eq!(track.swing, 50);
+    assert_eq!(track.track_speed, TrackSpeed::Fraction(1, 1));
+    assert_eq!(
+        track.steps[5],
+        Step {
+            number: 5,
+            ..Step::default()
+        }
+    );
+    assert_eq!(pattern.midi_track(0).steps[5].number, 5);
+    assert!(MidiStep::default().is_empty());
+    assert_eq!(Track::<Step>::new(0, 0).with_num_steps(0).steps.len(), 1);
+    assert_eq!(Track::<Step>::new(0, 0).with_num_steps(100).steps.len(), 64);
+
+    let pattern = Pattern::new(1).with_audio_track(
+        Track::new(2, 1)
+            .with_num_steps(32)
+            .with_swing(60)
+            .with_play_mode(PlayMode::Reverse)
+            .with_step(Step::new_note(20, 61).with_sample(3).with_volume(8000))
+            .active(),
+    );
+    let track = pattern.active_audio_track(2).unwrap();
+    assert_eq!(track.variation, 1);
+    assert_eq!(track.steps.len(), 32);
+    assert_eq!(track.steps[20].to_string(), "20 C#4 S003 V+2.0dB");
+    assert!(!pattern.audio_tracks[2][0].as_ref().unwrap().is_default);
+
+    let step = MidiStep::new_note(0, 60).with_cc(74, 10).with_program(3);
+    assert_eq!(step.to_string(), "00 C4 PG3 CC74:10");
+
+    // Tracks outside of a pattern's slots are rejected with a message
+    let panics = |f: fn()| std::panic::catch_unwind(f).is_err();
+    assert!(panics(|| {
+        Pattern::new(0).with_audio_track(Track::new(8, 0));
+    }));
+    assert!(panics(|| {
+        Pattern::new(0).with_midi_track(Track::new(0, 16));
+    }));
+    assert!(panics(|| {
+        MidiStep::new_note(0, 60).with_cc(1, 10);
+    }));
+}
+
+#[test]
+fn test_sd_card() {
+    let dir = std::env::temp_dir().join("play-files-sd-card");
+    let _ = std::fs::remove_dir_all(&dir);
+    let projects = dir.join("Projects");
+    _400BPM.write(&projects.join("b")).unwrap();
+    TEST_1.write(&projects.join("a")).unwrap();
+    std::fs::create_dir_all(projects.join("not a project")).unwrap();
+    std::fs::create_dir_all(projects.join("damaged")).unwrap();
+    let settings = TEST_1.settings.to_bytes();
+    std::fs::write(projects.join("damaged").join("settings"), &settings[..20]).unwrap();
+    let kicks = dir.join("Samples").join("Drums").join("Kick");
+    std::fs::create_dir_all(&kicks).unwrap();
+    std::fs::write(kicks.join("kick.wav"), []).unwrap();
+    std::fs::write(kicks.join("notes.txt"), []).unwrap();
+    std::fs::write(dir.join("Samples").join("snare.WAV"), []).unwrap();
+
+    let card = SdCard::new(&dir);
+    let mut entries = card.projects().unwrap();
+    let (damaged, entry) = entries.pop().unwrap();
+    assert_eq!(damaged, projects.join("damaged"));
+    assert!(entry.is_err());
+    let entries: Vec<ProjectEntry> = entries.into_iter().map(|(_, e)| e.unwrap()).collect();
+    let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, vec![TEST_1.settings.name.as_str(), "400 bpm"]);
+    assert_eq!(entries[1].bpm, 400.0);
+    assert!(entries[0].modified > std::time::SystemTime::UNIX_EPOCH);
+    assert_eq!(&entries[0].load().unwrap(), &*TEST_1);
+
+    let library = card.sample_library().unwrap().unwrap();
+    let folders: Vec<&str> = library.folders.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(folders, vec!["", "Drums/Kick"]);
+    assert_eq!(
+        library.folder("Drums/Kick").unwrap().samples,
+        vec![kicks.join("kick.wav")]
+    );
+
+    assert!(SdCard::new(&projects).projects().is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_lazy_project() {
+    let project = LazyProject::open(Path::new("./examples/projects/The demo")).unwrap();
+    assert_eq!(project.settings, THE_DEMO.settings);
+    let mut numbers = project.pattern_numbers();
+    numbers.sort();
+    let mut expected: Vec<u8> = THE_DEMO.patterns.iter().map(|p| p.number).collect();
+    expected.sort();
+    assert_eq!(numbers, expected);
+    let first = project.pattern(numbers[0]).unwrap().unwrap();
+    assert_eq!(first, THE_DEMO.pattern(numbers[0]).unwrap());
+    // Later uses get the same pattern without reading it again
+    assert!(std::ptr::eq(
+        first,
+        project.pattern(numbers[0]).unwrap().unwrap()
+    ));
+    assert!(project.pattern(200).is_none());
+    assert_eq!(&project.load().unwrap(), &*THE_DEMO);
+
+    // A pattern that can't be read doesn't stop the others from being read
+    let dir = std::env::temp_dir().join("play-files-lazy");
+    let _ = std::fs::remove_dir_all(&dir);
+    THE_DEMO.write(&dir).unwrap();
+    let broken = dir.join("patterns").join(format!("{}.pattern", numbers[1]));
+    std::fs::write(broken, [0x0A, 0x03, 0x0A]).unwrap();
+    let project = LazyProject::open(&dir).unwrap();
+    let failed: Vec<u8> = project
+        .patterns()
+        .filter(|(_, p)| p.is_err())
+        .map(|(n, _)| n)
+        .collect();
+    assert_eq!(failed, vec![numbers[1]]);
+    let error = project.pattern(numbers[1]).unwrap().unwrap_err();
+    assert!(format!("{:?}", error).contains(&format!("{}.pattern", numbers[1])));
+    assert!(project.pattern(numbers[0]).unwrap().is_ok());
+    assert!(project.load().is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_from_files() {
+    let dir = Path::new("./examples/projects/The demo");
+    assert_eq!(&Project::from_files(dir).unwrap(), &*THE_DEMO);
+
+    // As a web service might receive a project
+    let mut files = std::collections::BTreeMap::new();
+    for sub_dir in ["", "samples", "patterns"] {
+        for entry in std::fs::read_dir(dir.join(sub_dir)).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_file() {
+                let name = entry.file_name().to_str().unwrap().to_string();
+                let path = if sub_dir.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", sub_dir, name)
+                };
+                files.insert(path, std::fs::read(entry.path()).unwrap());
+            }
+        }
+    }
+    assert_eq!(&Project::from_files(&files).unwrap(), &*THE_DEMO);
+
+    let settings = std::io::Cursor::new(files["settings"].clone());
+    assert_eq!(Settings::from_reader(settings).unwrap(), THE_DEMO.settings);
+    let pattern = Pattern::from_bytes(&files["patterns/0.pattern"], 0).unwrap();
+    assert_eq!(
+        pattern.active_audio_track(0),
+        THE_DEMO.pattern(0).unwrap().active_audio_track(0)
+    );
+
+    files.remove("settings");
+    assert!(Project::from_files(&files).is_err());
+}
+
+#[test]
+fn test_vfs() {
+    // Names with glob metacharacters are read as they are
+    let dir = std::env::temp_dir().join("play-files-vfs [1+2] *");
+    let _ = std::fs::remove_dir_all(&dir);
+    THE_DEMO.write(&dir).unwrap();
+    assert_eq!(&Project::read(&dir).unwrap(), &*THE_DEMO);
+    let patterns = Pattern::read_patterns(&dir.join("patterns")).unwrap();
+    assert_eq!(patterns, THE_DEMO.patterns);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut files = std::collections::HashMap::new();
+    THE_DEMO.write_files(&mut files).unwrap();
+    assert!(files.contains_key("patterns/0.pattern"));
+    // Files that aren't project files are ignored
+    files.write_file("patterns/0-1.track", &[0]).unwrap();
+    files.write_file("patterns/notes.txt", &[0]).unwrap();
+    assert_eq!(&Project::from_files(&files).unwrap(), &*THE_DEMO);
+}
+
+/// Project files held in memory, some of which can't be read
+struct Unreadable {
+    files: std::collections::HashMap<String, Vec<u8>>,
+    unreadable: Vec<&'static str>,
+}
+
+impl Vfs for Unreadable {
+    fn read_file(&self, path: &str) -> std::io::Result<Option<Vec<u8>>> {
+        if self.unreadable.contains(&path) {
+            return Err(std::io::Error::other("unreadable"));
+        }
+        self.files.read_file(path)
+    }
+
+    fn list_dir(&self, dir: &str) -> std::io::Result<Vec<String>> {
+        self.files.list_dir(dir)
+    }
+
+    fn write_file(&mut self, path: &str, bytes: &[u8]) -> std::io::Result<()> {
+        self.files.write_file(path, bytes)
+    }
+}
+
+#[test]
+fn test_read_errors() {
+    let mut files = Unreadable {
+        files: std::collections::HashMap::new(),
+        unreadable: vec!["patterns/1.pattern", "patterns/0-0-1.track"],
+    };
+    THE_DEMO.write_files(&mut files).unwrap();
+    // Every file that can't be read is reported
+    let e = Project::from_files(&files).unwrap_err().to_string();
+    assert!(e.contains("patterns/1.pattern") && e.contains("patterns/0-0-1.track"));
+
+    #[cfg(feature = "rayon")]
+    {
+        assert_eq!(Project::from_files_par(&files).unwrap_err().to_string(), e);
+        files.unreadable.clear();
+        assert_eq!(&Project::from_files_par(&files).unwrap(), &*THE_DEMO);
+    }
+}
+
+#[test]
+fn test_step_ref() {
+    let dir = Path::new("./examples/projects/The demo/patterns");
+    let bytes = std::fs::read(dir.join("0.pattern")).unwrap();
+    let pattern = THE_DEMO.pattern(0).unwrap();
+    for track in 0..8 {
+        let steps = StepRef::pattern_file(&bytes, track).unwrap();
+        assert_eq!(steps.len(), 64);
+        let expected = &pattern.active_audio_track(track).unwrap().steps;
+        for (step, expected) in steps.iter().zip(expected) {
+            assert_eq!(&step.to_step(), expected);
+            assert_eq!(step.is_empty(), expected.is_empty());
+            assert_eq!(step.sample(), expected.sample);
+        }
+    }
+    assert!(StepRef::pattern_file(&bytes, 8).is_err());
+
+    let bytes = std::fs::read(dir.join("0-0-1.track")).unwrap();
+    let steps = StepRef::track_file(&bytes).unwrap();
+    let track = pattern.audio_tracks[0][1].as_ref().unwrap();
+    for (step, expected) in steps.iter().zip(&track.steps) {
+        assert_eq!(&step.to_step(), expected);
+    }
+
+    assert!(StepRef::track_file(&bytes[..bytes.len() / 2]).is_err());
+    assert!(StepRef::pattern_file(&[], 0).is_err());
+    assert!(StepRef::pattern_file(&[0x0A, 0x7F], 1).is_err());
+    // A wrong tag, and a wrong number of values
+    assert!(StepRef::track_file(&[0x0B, 0x30, 0x0A, 0x2C]).is_err());
+    let mut step = vec![0x0A, 0x30, 0x0A, 0x34];
+    step.extend([0; 46]);
+    assert!(StepRef::track_file(&step).is_err());
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn test_zip() {
+    use std::io::{Cursor, Write};
+
+    let sample = SampleFile {
+        slot: 3,
+        file_name: "[003] kick.wav".to_string(),
+        bytes: b"RIFF".to_vec(),
+    };
+    let zip = THE_DEMO
+        .to_zip(Cursor::new(vec![]), std::slice::from_ref(&sample))
+        .unwrap();
+    let archive = zip::ZipArchive::new(Cursor::new(zip.get_ref())).unwrap();
+    assert!(archive.file_names().all(|n| n.starts_with("The demo/")));
+    assert!(archive.file_names().any(|n| n == "The demo/settings"));
+    let project = Project::from_zip(Cursor::new(zip.get_ref())).unwrap();
+    assert_eq!(&project, &*THE_DEMO);
+    let samples = SampleFile::from_zip(Cursor::new(zip.get_ref())).unwrap();
+    assert_eq!(samples, vec![sample.clone()]);
+
+    // The folder is never hidden or outside of the archive
+    for (name, folder) in [(".foo", "foo/"), ("..", "project/"), ("a/..", "a_../")] {
+        let mut project = THE_DEMO.clone();
+        project.settings.name = name.to_string();
+        let zip = project.to_zip(Cursor::new(vec![]), &[]).unwrap();
+        let archive = zip::ZipArchive::new(Cursor::new(zip.get_ref())).unwrap();
+        assert!(
+            archive.file_names().all(|n| n.starts_with(folder)),
+            "{}",
+            name
+        );
+        assert_eq!(
+            Project::from_zip(Cursor::new(zip.get_ref())).unwrap(),
+            project
+        );
+    }
+
+    // Without a wrapping folder, and with the files macOS adds
+    let mut archive = zip::ZipWriter::new(Cursor::new(vec![]));
+    let options = zip::write::SimpleFileOptions::default();
+    for (path, bytes) in [
+        ("settings", THE_DEMO.settings.to_bytes()),
+        ("samples/samplesMetadata", THE_DEMO.samples.to_bytes()),
+        ("__MACOSX/._settings", vec![0]),
+    ] {
+        archive.start_file(path, options).unwrap();
+        archive.write_all(&bytes).unwrap();
+    }
+    let zip = archive.finish().unwrap();
+    let project = Project::from_zip(Cursor::new(zip.get_ref())).unwrap();
+    assert_eq!(project.settings, THE_DEMO.settings);
+    assert!(project.patterns.is_empty());
+
+    let path = std::env::temp_dir().join("play-files-zip.zip");
+    THE_DEMO
+        .write_zip(&path, std::slice::from_ref(&sample))
+        .unwrap();
+    assert_eq!(&Project::read_zip(&path).unwrap(), &*THE_DEMO);
+    assert_eq!(SampleFile::read_zip(&path).unwrap(), vec![sample]);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(Project::from_zip(Cursor::new(b"not a zip".to_vec())).is_err());
+}
+
+#[test]
+fn test_sample_slots() {
+    let wav = |n: u8| [b"RIFF".as_slice(), &[n, 0, 0, 0], b"WAVE"].concat();
+    let mut project = Project::new("Samples");
+    project.patterns[0] = Pattern::new(0)
+        .with_audio_track(
+            Track::new(0, 0)
+                .active()
+                .with_step(Step::new_note(0, 60))
+                .with_step(Step::new_note(4, 60).with_sample(2)),
+        )
+        .with_audio_track(Track::new(0, 1).with_step(Step::new_note(8, 60).with_sample(2)));
+    project.samples.slot_folders[2] = 3;
+    let mut files = vec![
+        SampleFile::new(0, "kick", wav(0)),
+        SampleFile::new(2, "snare.wav", wav(2)),
+    ];
+    assert_eq!(files[1].file_name, "[002] snare.wav");
+    assert_eq!(files[1].name(), "snare");
+
+    let slot = project.add_sample(&mut files, "hat.wav", wav(1)).unwrap();
+    assert_eq!(slot, 1);
+    assert_eq!(files[1].file_name, "[001] hat.wav");
+    assert!(project
+        .add_sample(&mut files, "text", b"text".to_vec())
+        .is_err());
+
+    project.replace_sample(&mut files, 2, wav(3)).unwrap();
+    assert_eq!(files[2].bytes, wav(3));
+    assert!(project.replace_sample(&mut files, 5, wav(3)).is_err());
+
+    // Snare first
+    project.move_sample(&mut files, 2, 0).unwrap();
+    let names: Vec<_> = files.iter().map(|f| f.file_name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["[000] snare.wav", "[001] kick.wav", "[002] hat.wav"]
+    );
+    assert_eq!(project.samples.slot_folders[0], 3);
+    let pattern = &project.patterns[0];
+    assert_eq!(pattern.audio_track(0).steps[0].sample, 1);
+    assert_eq!(pattern.audio_track(0).steps[4].sample, 0);
+    assert_eq!(
+        pattern.audio_tracks[0][1].as_ref().unwrap().steps[8].sample,
+        0
+    );
+    assert!(project.move_sample(&mut files, 0, 256).is_err());
+
+    // Kick
+    assert_eq!(project.remove_sample(&mut files, 1).unwrap(), 1);
+    assert_eq!(files.len(), 2);
+    assert!(project.patterns[0].audio_track(0).steps[0].is_empty());
+    assert_eq!(project.used_samples().into_iter().collect::<Vec<_>>(), [0]);
+    assert!(project.validate().is_empty());
+
+    let dir = std::env::temp_dir().join("play-files-sample-slots");
+    let _ = std::fs::remove_dir_all(&dir);
+    project.write(&dir).unwrap();
+    std::fs::write(dir.join("samples/[009] old.wav"), wav(9)).unwrap();
+    SampleFile::write_dir(&dir, &files).unwrap();
+    assert_eq!(SampleFile::read_dir(&dir).unwrap(), files);
+    assert_eq!(&Project::read(&dir).unwrap(), &project);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_prune_samples() {
+    use play_files::render::{AudioBuffer, SampleFormat};
+
+    let sine = AudioBuffer {
+        sample_rate: 44100,
+        channels: vec![(0..100).map(|i| (i as f32 / 10.0).sin() / 2.0).collect()],
+    };
+    let mut wav = vec![];
+    sine.write_wav_to(&mut wav, SampleFormat::Int16).unwrap();
+    // With a metadata chunk
+    let tagged = [wav.as_slice(), b"LIST\x04\0\0\0abcd"].concat();
+    let track = [1, 3, 5, 7, 9]
+        .into_iter()
+        .enumerate()
+        .fold(Track::new(0, 0).active(), |track, (n, sample)| {
+            track.with_step(Step::new_note(n, 60).with_sample(sample))
+        });
+    let mut project = Project::new("Prune");
+    project.patterns[0] = Pattern::new(0).with_audio_track(track);
+    project.samples.slot_folders[7] = 2;
+    let mut files = vec![
+        SampleFile::new(0, "unused", wav.clone()),
+        SampleFile::new(1, "kick", wav.clone()),
+        SampleFile::new(3, "kick copy", wav),
+        SampleFile::new(5, "kick tagged", tagged),
+        SampleFile::new(7, "snare", b"RIFF\0\0\0\0WAVE".to_vec()),
+    ];
+    let size = |slot: usize| files[slot].bytes.len();
+    let bytes_saved = size(0) + size(2) + size(3);
+
+    let report = project.prune_samples(&mut files);
+    assert_eq!(report.unused, [0]);
+    assert_eq!(report.duplicates, [(3, 1), (5, 1)]);
+    assert_eq!(report.bytes_saved, bytes_saved);
+    let names: Vec<_> = files.iter().map(|f| f.file_name.as_str()).collect();
+    assert_eq!(names, ["[000] kick.wav", "[001] snare.wav"]);
+    // Slot 9 has no file, but keeps a slot for its note
+    let samples: Vec<_> = project.patterns[0].audio_track(0).steps[0..5]
+        .iter()
+        .map(|s| s.sample)
+        .collect();
+    assert_eq!(samples, [0, 0, 0, 1, 2]);
+    assert_eq!(project.samples.slot_folders[1], 2);
+    assert_eq!(project.samples.slot_folders[7], 0);
+
+    assert_eq!(project.prune_samples(&mut files), PruneReport::default());
+}
+
+#[test]
+fn test_malformed_bytes() {
+    let dir = Path::new("./examples/projects/The demo");
+    let settings = std::fs::read(dir.join("settings")).unwrap();
+    let samples = std::fs::read(dir.join("samples/samplesMetadata")).unwrap();
+    let pattern = std::fs::read(dir.join("patterns/0.pattern")).unwrap();
+    let track = std::fs::read(dir.join("patterns/0-0-1.track")).unwrap();
+    let mut seed = 1u32;
+    let garbage: Vec<u8> = (0..4096)
+        .map(|_| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            (seed >> 24) as u8
+        })
+        .collect();
+
+    // None of these may panic
+    for bytes in [&settings, &samples, &pattern, &track, &garbage] {
+        for len in (0..bytes.len()).step_by(37) {
+            let bytes = &bytes[..len];
+            let _ = Settings::from_bytes(bytes);
+            let _ = Samples::from_bytes(bytes);
+            let _ = Pattern::from_bytes(bytes, 0);
+            let _ = Track::<Step>::from_bytes(bytes, 0, 1);
+            let _ = Track::<MidiStep>::from_bytes(bytes, 0, 1);
+        }
+    }
+
+    assert!(Settings::from_bytes(&settings[..settings.len() / 2]).is_err());
+    assert!(Settings::from_bytes(&[0x12, 0xFF, b'a']).is_err());
+    assert!(Samples::from_bytes(&samples[..samples.len() - 1]).is_err());
+    assert!(Samples::from_bytes(&[0x0A, 0x02, 0xFF, 0x00]).is_err());
+    assert!(Pattern::from_bytes(&[], 0).is_err());
+    assert!(Pattern::from_bytes(&pattern[..pattern.len() / 2], 0).is_err());
+    assert!(Pattern::from_bytes(&garbage, 0).is_err());
+    assert!(Track::<Step>::from_bytes(&track[..track.len() / 2], 0, 1).is_err());
+    assert!(Track::<Step>::from_bytes(&garbage, 0, 1).is_err());
+    assert!(Track::<MidiStep>::from_bytes(&garbage, 0, 1).is_err());
+
+    // Variations are numbered 0-15
+    let mut pattern = Pattern::new(0);
+    pattern.audio_tracks[0][0].as_mut().unwrap().variation = 16;
+    assert!(Pattern::from_bytes(&pattern.to_bytes().unwrap(), 0).is_err());
+
+    // A note's bitmask of set values is in bytes 5-7 of its rest
+    let mut step = MidiStep::new_note(0, 60);
+    step.rest.truncate(4);
+    let track = Track::new(0, 0).with_step(step).to_bytes();
+    assert!(Track::<MidiStep>::from_bytes(&track, 0, 0).is_err());
+}
+
+#[test]
+fn test_diff() {
+    assert_eq!(diff(&TEST_1, &TEST_1), vec![]);
+
+    let mut project = TEST_1.clone();
+    project.settings.bpm = 130.0;
+    project.settings.jack_cc_mapping[2].cutoff = 10;
+    project.samples.slot_folders[12] = 2;
+    let track = project.patterns[0].audio_tracks[1][0].as_mut().unwrap();
+    track.steps[7].volume = 7200;
+    let changes: Vec<String> = diff(&TEST_1, &project)
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            "settings: bpm 120 → 130",
+            "CC mapping for Jack 3: cutoff CC74 → CC10",
+            "sample slot 12: folder Bass → HiHat",
+            "pattern 0 audio track 1 variation 0 step 7: volume +0.0dB → -2.0dB",
+        ]
+    );
+
+    let mut project = TEST_1.clone();
+    project.settings.usb_cc_mapping.pop();
+    project.samples.folders.push("Vocals".to_string());
+    project.samples.slot_folders.truncate(254);
+    let changes: Vec<String> = diff(&TEST_1, &project)
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            "CC mapping for USB 16: removed",
+            format!("sample folder {}: added", TEST_1.samples.folders.len()).as_str(),
+            "sample slot 254: removed",
+            "sample slot 255: removed",
+        ]
+    );
+}
+
+#[test]
+fn test_merge() {
+    let base = &*TEST_1;
+    let (merged, conflicts) = merge(base, base, base);
+    assert_eq!(&merged, base);
+    assert_eq!(conflicts, vec![]);
+
+    let mut ours = base.clone();
+    ours.settings.bpm = 130.0;
+    let track = ours.patterns[0].audio_tracks[1][0].as_mut().unwrap();
+    track.steps[7].volume = 7200;
+    track.swing = 60;
+    let mut theirs = base.clone();
+    theirs.settings.jack_cc_mapping[2].cutoff = 10;
+    let track = theirs.patterns[0].audio_tracks[1][0].as_mut().unwrap();
+    track.steps[7].pan = -5000;
+    track.swing = 40;
+
+    let (merged, conflicts) = merge(base, &ours, &theirs);
+    assert_eq!(merged.settings.bpm, 130.0);
+    assert_eq!(merged.settings.jack_cc_mapping[2].cutoff, 10);
+    let track = merged.patterns[0].audio_tracks[1][0].as_ref().unwrap();
+    assert_eq!(track.steps[7].volume, 7200);
+    assert_eq!(track.steps[7].pan, -5000);
+    assert_eq!(track.swing, 60);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(
+        conflicts[0].to_string(),
+        format!(
+            "pattern 0 audio track 1 variation 0: swing is 60% in ours and 40% in theirs (was {}%)",
+            base.patterns[0].audio_track(1).swing
+        )
+    );
+
+    // Conflicting values are in the units shown on the device
+    let mut ours = base.clone();
+    let track = ours.patterns[0].audio_tracks[1][0].as_mut().unwrap();
+    track.steps[7].volume = 6800;
+    let mut theirs = base.clone();
+    let track = theirs.patterns[0].audio_tracks[1][0].as_mut().unwrap();
+    track.steps[7].volume = 7200;
+    let (_, conflicts) = merge(base, &ours, &theirs);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].field, "volume");
+    assert_eq!(conflicts[0].ours, units::format_volume(6800));
+    assert_eq!(conflicts[0].theirs, units::format_volume(7200));
+    assert!(conflicts[0].ours.ends_with("dB"));
+
+    // Changing which variation is active doesn't conflict with editing a variation
+    let mut ours = base.clone();
+    let variations = &mut ours.patterns[0].audio_tracks[0];
+    let other = variations
+        .iter()
+        .flatten()
+        .find(|t| !t.is_default)
+        .unwrap()
+        .variation;
+    for track in variations.iter_mut().flatten() {
+        track.is_default = track.variation == other;
+    }
+    let mut theirs = base.clone();
+    theirs.patterns[0].audio_tracks[0][other]
+        .as_mut()
+        .unwrap()
+        .steps[0]
+        .note = 62;
+    let (merged, conflicts) = merge(base, &ours, &theirs);
+    assert_eq!(conflicts, vec![]);
+    let track = merged.patterns[0].active_audio_track(0).unwrap();
+    assert_eq!(track.variation, other);
+    assert_eq!(track.steps[0].note, 62);
+    assert!(merged.patterns[0].to_bytes().is_ok());
+
+    // Setting different values of a MIDI note doesn't conflict, though both change its bitmask
+    let round_trip = |p: &Pattern| Pattern::from_bytes(&p.to_bytes().unwrap(), p.number).unwrap();
+    let note = MidiStep::new_note(0, 60);
+    let with_note = |step: MidiStep| {
+        let mut project = base.clone();
+        project.patterns[0] =
+            round_trip(&Pattern::new(0).with_midi_track(Track::new(0, 0).active().with_step(step)));
+        project
+    };
+    let (merged, conflicts) = merge(
+        &with_note(note.clone()),
+        &with_note(note.clone().with_cc(12, 10)),
+        &with_note(note.with_pitch_bend(500)),
+    );
+    assert_eq!(conflicts, vec![]);
+    let pattern = round_trip(&merged.patterns[0]);
+    let step = &pattern.midi_track(0).steps[0];
+    assert_eq!(step.cc12, Some(10));
+    assert_eq!(step.pitch_bend, Some(500));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() {
+    for project in [&*TEST_1, &*BELIEVE_IT, &*BLANK] {
+        let json = serde_json::to_string(project).unwrap();
+        let parsed: Project = serde_json::from_str(&json).unwrap();
+        assert_eq!(&parsed, project);
+    }
+}
@@ -0,0 +1,60 @@
+# Build and install the module with `maturin develop`, then run `pytest`
+from pathlib import Path
+
+import pytest
+
+import play_files
+
+PROJECTS = Path(__file__).parent.parent.parent / "examples" / "projects"
+
+
+@pytest.fixture(scope="module")
+def demo():
+    return play_files.Project.read(str(PROJECTS / "The demo"))
+
+
+def test_project(demo):
+    assert demo.settings.name == "The demo"
+    assert demo.settings.bpm == 139.0
+    assert len(demo.patterns) == 10
+    assert demo.pattern(0).number == 0
+    assert demo.pattern(99) is None
+
+
+def test_tracks(demo):
+    pattern = demo.pattern(0)
+    assert len(pattern.audio_tracks) == 8
+    assert len(pattern.midi_tracks) == 8
+    assert all(t.is_active for t in pattern.audio_tracks)
+
+    track = pattern.audio_tracks[0]
+    assert not track.is_midi
+    assert track.play_mode == "Forward"
+    assert track.track_speed == "1/1"
+    assert isinstance(track.steps[0], play_files.Step)
+    assert track.steps[0].volume == 6600
+    assert track.steps[0].sample == 5
+
+    midi = pattern.midi_tracks[0]
+    assert midi.is_midi
+    assert isinstance(midi.steps[0], play_files.MidiStep)
+    assert midi.steps[0].channel == "Jack 1"
+
+
+def test_to_records(demo):
+    records = demo.to_records()
+    steps = sum(len(t.steps) for p in demo.patterns for t in p.variations)
+    assert len(records) == steps
+    assert records[0]["pattern"] == 0
+    assert records[0]["kind"] == "audio"
+    assert records[0]["track"] == 0
+    assert records[0]["volume"] == 6600
+    assert {r["kind"] for r in records} == {"audio", "midi"}
+    assert len(demo.pattern(0).to_records()) == sum(
+        len(t.steps) for t in demo.pattern(0).variations
+    )
+
+
+def test_parse_error():
+    with pytest.raises(play_files.ParseError):
+        play_files.Project.read(str(PROJECTS / "Missing"))
@@ -0,0 +1,60 @@
+//! Run under Node with `cargo test --target wasm32-unknown-unknown --no-default-features
+//! --features wasm`, which needs `wasm-bindgen-test-runner` from `wasm-bindgen-cli`.
+#![cfg(target_arch = "wasm32")]
+
+use js_sys::{Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+use play_files::wasm::*;
+
+const SETTINGS: &[u8] = include_bytes!("../examples/projects/The demo/settings");
+const SAMPLES: &[u8] = include_bytes!("../examples/projects/The demo/samples/samplesMetadata");
+const PATTERN: &[u8] = include_bytes!("../examples/projects/The demo/patterns/0.pattern");
+const TRACK: &[u8] = include_bytes!("../examples/projects/The demo/patterns/0-0-1.track");
+
+fn get(value: &JsValue, path: &[&str]) -> JsValue {
+    path.iter().fold(value.clone(), |v, key| {
+        Reflect::get(&v, &JsValue::from_str(key)).unwrap()
+    })
+}
+
+#[wasm_bindgen_test]
+fn test_parse_project() {
+    let files = Object::new();
+    for (path, bytes) in [
+        ("The demo/settings", SETTINGS),
+        ("The demo/samples/samplesMetadata", SAMPLES),
+        ("The demo/samples/.DS_Store", &[0][..]),
+        ("The demo/patterns/0.pattern", PATTERN),
+        ("The demo/patterns/0-0-1.track", TRACK),
+    ] {
+        Reflect::set(&files, &path.into(), &Uint8Array::from(bytes)).unwrap();
+    }
+    let project = parse_project(&files).unwrap();
+    assert_eq!(get(&project, &["settings", "name"]), "The demo");
+    let patterns: Array = get(&project, &["patterns"]).into();
+    assert_eq!(patterns.length(), 1);
+    let variations: Array = get(&patterns.get(0), &["audio_tracks"]).into();
+    let variations: Array = variations.get(0).into();
+    assert!(variations.get(1).is_object());
+    assert!(variations.get(2).is_null());
+
+    Reflect::delete_property(&files, &"The demo/settings".into()).unwrap();
+    assert!(parse_project(&files).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_parse_pattern_and_track() {
+    let pattern = parse_pattern(PATTERN, 0).unwrap();
+    assert_eq!(get(&pattern, &["number"]), 0);
+    let midi: Array = get(&pattern, &["midi_tracks"]).into();
+    assert_eq!(midi.length(), 8);
+
+    let track = parse_track(TRACK, 0, 1).unwrap();
+    assert_eq!(get(&track, &["variation"]), 1);
+    let steps: Array = get(&track, &["steps"]).into();
+    assert!(steps.length() > 0);
+    assert!(get(&steps.get(0), &["volume"]).as_f64().is_some());
+    assert!(parse_track(TRACK, 16, 0).is_err());
+}